@@ -0,0 +1,92 @@
+//! Plain bincode against the compressed encoding, for notes of different lengths.
+
+fn notes() -> Vec<(&'static str, note::Signed<note::Note>)> {
+    let key_pair = libp2p::identity::Keypair::ed25519_from_bytes([7; 32]).expect("invalid key");
+    let now = time::OffsetDateTime::now_utc();
+    let note = |msg: String| {
+        note::Note {
+            topic: "ops".to_string(),
+            msg,
+            created_at: now,
+            hlc: hlc::Timestamp::from_wall_clock(now),
+            parents: Vec::new(),
+            attachments: Vec::new(),
+        }
+        .sign(&key_pair)
+        .expect("failed to sign note")
+    };
+
+    vec![
+        ("short", note("Lunch at noon?".to_string())),
+        (
+            "paragraph",
+            note("The deploy went out at ten, but the migration is still running on the replicas, so \
+                  hold off on anything that touches the accounts table until it's done. "
+                .repeat(4)),
+        ),
+        (
+            "paste",
+            note(
+                (0..2_000)
+                    .map(|line| format!("2024-05-10T12:{:02}:00Z INFO request handled in {}ms\n", line % 60, line % 97))
+                    .collect(),
+            ),
+        ),
+    ]
+}
+
+fn encode(c: &mut criterion::Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, note) in notes() {
+        let plain = note.encode_to_vec().expect("failed to encode");
+        let compressed = note.encode_compressed().expect("failed to encode");
+        eprintln!(
+            "{name}: {} bytes plain, {} bytes compressed",
+            plain.len(),
+            compressed.len()
+        );
+
+        group.throughput(criterion::Throughput::Bytes(plain.len() as u64));
+        group.bench_with_input(
+            criterion::BenchmarkId::new("bincode", name),
+            &note,
+            |b, note| b.iter(|| note.encode_to_vec()),
+        );
+        group.bench_with_input(
+            criterion::BenchmarkId::new("compressed", name),
+            &note,
+            |b, note| b.iter(|| note.encode_compressed()),
+        );
+    }
+    group.finish();
+}
+
+fn decode(c: &mut criterion::Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, note) in notes() {
+        let plain = note.encode_to_vec().expect("failed to encode");
+        let compressed = note.encode_compressed().expect("failed to encode");
+
+        group.throughput(criterion::Throughput::Bytes(plain.len() as u64));
+        group.bench_with_input(
+            criterion::BenchmarkId::new("bincode", name),
+            &plain,
+            |b, data| b.iter(|| note::Signed::<note::Note>::decode(data.as_slice())),
+        );
+        group.bench_with_input(
+            criterion::BenchmarkId::new("compressed", name),
+            &compressed,
+            |b, data| b.iter(|| note::Signed::<note::Note>::decode_compressed(data)),
+        );
+    }
+    group.finish();
+}
+
+criterion::criterion_group!(benches, encode, decode);
+criterion::criterion_main!(benches);
+
+use n2p::hlc;
+use n2p::note;
+use n2p::note::Decode as _;
+use n2p::note::Encode as _;
+use n2p::note::Sign as _;
@@ -0,0 +1,88 @@
+//! Checking a backlog of signatures one by one, in batches, and in batches on the blocking pool.
+
+fn notes(count: usize) -> Vec<note::Signed<note::Note>> {
+    let key_pairs: Vec<_> = (0..16)
+        .map(|_| libp2p::identity::Keypair::generate_ed25519())
+        .collect();
+    let now = time::OffsetDateTime::now_utc();
+
+    (0..count)
+        .map(|i| {
+            note::Note {
+                topic: "ops".to_string(),
+                msg: format!("Note number {i} of the backlog."),
+                created_at: now,
+                hlc: hlc::Timestamp::from_wall_clock(now),
+                parents: Vec::new(),
+                attachments: Vec::new(),
+            }
+            .sign(&key_pairs[i % key_pairs.len()])
+            .expect("failed to sign note")
+        })
+        .collect()
+}
+
+fn verify(c: &mut criterion::Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start runtime");
+    let mut group = c.benchmark_group("verify");
+    for count in [64, 1024, 4096] {
+        let notes = notes(count);
+
+        group.throughput(criterion::Throughput::Elements(count as u64));
+        group.bench_with_input(
+            criterion::BenchmarkId::new("one by one", count),
+            &notes,
+            |b, notes| b.iter(|| notes.iter().filter(|note| note.verify()).count()),
+        );
+        group.bench_with_input(
+            criterion::BenchmarkId::new("batches", count),
+            &notes,
+            |b, notes| {
+                b.iter(|| {
+                    notes
+                        .chunks(verify::MAX_BATCH_SIZE)
+                        .flat_map(verify::verify_batch)
+                        .filter(|valid| *valid)
+                        .count()
+                })
+            },
+        );
+        group.bench_with_input(
+            criterion::BenchmarkId::new("verifier", count),
+            &notes,
+            |b, notes| {
+                b.iter_batched(
+                    || notes.clone(),
+                    |notes| {
+                        runtime.block_on(async {
+                            let mut verifier = verify::Verifier::new();
+                            for note in notes {
+                                verifier.push((), note);
+                            }
+                            let mut valid = 0;
+                            while !verifier.is_idle() {
+                                valid += verifier
+                                    .next()
+                                    .await
+                                    .iter()
+                                    .filter(|verified| verified.valid)
+                                    .count();
+                            }
+                            valid
+                        })
+                    },
+                    criterion::BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion::criterion_group!(benches, verify);
+criterion::criterion_main!(benches);
+
+use n2p::hlc;
+use n2p::note;
+use n2p::note::Sign as _;
+use n2p::verify;
@@ -2,17 +2,20 @@ pub struct App {
     controller: controller::Controller,
     components: Components,
     focus: Focus,
-    key_pair: identity::Keypair,
+    signing_key: note::SigningKey,
+    side_pane: Option<SidePane>,
     exit: bool,
 }
 
 impl App {
-    pub fn new() -> anyhow::Result<Self> {
+    /// Times are shown in `local_offset`.
+    pub fn new(config: controller::Config, local_offset: time::UtcOffset) -> anyhow::Result<Self> {
         Ok(Self {
-            controller: controller::Controller::new()?,
-            components: Components::new(),
+            signing_key: config.signing_key.clone(),
+            controller: controller::Controller::new(config)?,
+            components: Components::new(local_offset),
             focus: Focus::MessageInput,
-            key_pair: identity::Keypair::generate_ed25519(),
+            side_pane: None,
             exit: false,
         })
     }
@@ -20,6 +23,9 @@ impl App {
     pub async fn run(&mut self, terminal: &mut tui::Tui) -> anyhow::Result<()> {
         let mut event_stream = crossterm::event::EventStream::new();
         while !self.exit {
+            if let Some(topic) = self.components.chat_view.topic_read() {
+                self.controller.mark_read(topic);
+            }
             terminal.draw(|frame| self.render_frame(frame))?;
             tokio::select! {
                 _ = self.controller.poll() => {}
@@ -56,14 +62,57 @@ impl App {
             (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('t')) => {
                 self.focus = Focus::Topics;
             }
+            (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('n')) => {
+                self.focus = Focus::ChatView;
+            }
             (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('y')) => {
                 self.focus = Focus::MessageInput;
             }
+            (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('p')) => {
+                self.toggle_side_pane(SidePane::Peers);
+            }
+            (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('b')) => {
+                self.toggle_side_pane(SidePane::Blocklist);
+            }
+            (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('f')) => {
+                self.toggle_side_pane(SidePane::Search);
+            }
+            (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('k')) => {
+                self.components.backup.open(&self.signing_key);
+                self.focus = Focus::Backup;
+            }
             _event => {
                 let effect = self.components.update(self.focus, key_event);
                 match effect {
                     components::Effect::SendMessage(msg) => self.send_message(msg),
+                    components::Effect::SendAttachment(path) => self.send_attachment(&path),
                     components::Effect::ViewTopic(topic) => self.components.chat_view.view(topic),
+                    components::Effect::JumpToNote(topic, id) => {
+                        self.components.topics.select(&topic);
+                        self.components.chat_view.view(topic);
+                        self.components.chat_view.jump_to(id);
+                        self.focus = Focus::ChatView;
+                    }
+                    components::Effect::UpdateContact(pub_key, update) => self
+                        .controller
+                        .update_contact(pub_key, |contact| match update {
+                            components::ContactUpdate::Petname(petname) => {
+                                contact.petname = petname
+                            }
+                            components::ContactUpdate::Notes(notes) => contact.notes = notes,
+                            components::ContactUpdate::CycleTrust => {
+                                contact.trust = contact.trust.next()
+                            }
+                        }),
+                    components::Effect::UpdateBlocks(update) => self.update_blocks(update),
+                    components::Effect::SaveAttachments(attachments) => {
+                        for attachment in attachments {
+                            self.controller.save_attachment(attachment);
+                        }
+                    }
+                    components::Effect::RestoreIdentity(words, replace) => {
+                        self.restore_identity(&words, replace)
+                    }
                     components::Effect::Return => self.focus = Focus::MessageInput,
                     _ => (),
                 }
@@ -71,22 +120,103 @@ impl App {
         }
     }
 
+    fn toggle_side_pane(&mut self, pane: SidePane) {
+        if self.side_pane == Some(pane) {
+            self.side_pane = None;
+            self.focus = Focus::MessageInput;
+        } else {
+            self.side_pane = Some(pane);
+            self.focus = match pane {
+                SidePane::Peers => Focus::Peers,
+                SidePane::Blocklist => Focus::Blocklist,
+                SidePane::Search => Focus::Search,
+            };
+        }
+    }
+
+    fn update_blocks(&mut self, update: components::BlocksUpdate) {
+        match update {
+            components::BlocksUpdate::BlockAuthor(pub_key) => self.controller.block_author(pub_key),
+            components::BlocksUpdate::UnblockAuthor(pub_key) => {
+                self.controller.unblock_author(&pub_key)
+            }
+            components::BlocksUpdate::BlockPeer(peer_id) => self.controller.block_peer(peer_id),
+            components::BlocksUpdate::UnblockPeer(peer_id) => {
+                self.controller.unblock_peer(&peer_id)
+            }
+            components::BlocksUpdate::Mute(topic, pub_key) => self.controller.mute(topic, pub_key),
+            components::BlocksUpdate::Unmute(topic, pub_key) => {
+                self.controller.unmute(&topic, &pub_key)
+            }
+        }
+    }
+
+    fn restore_identity(&mut self, words: &str, replace: bool) {
+        match backup::from_words(words) {
+            Ok(key_pair) => match self.controller.restore_identity(&key_pair, replace) {
+                controller::Restore::Restored => {
+                    self.components.backup.restored();
+                    self.focus = Focus::MessageInput;
+                }
+                controller::Restore::Exists => self.components.backup.confirm(),
+                controller::Restore::Failed => (),
+            },
+            Err(err) => self
+                .controller
+                .set_status(format!("Failed to restore identity: {err}")),
+        }
+    }
+
     fn send_message(&mut self, msg: String) {
-        let now = time::OffsetDateTime::now_utc();
-        let created_at = time::PrimitiveDateTime::new(now.date(), now.time());
-        let note = note::Note {
-            topic: self
-                .components
-                .topics
-                .selected_topic()
-                .expect("No topic")
-                .to_string(),
-            msg,
-            created_at,
-        };
+        self.send_note(msg, Vec::new());
+    }
 
-        let signed = note.sign(&self.key_pair).expect("failed to sign note");
-        self.controller.send_note(signed);
+    fn send_attachment(&mut self, path: &std::path::Path) {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let attachment = std::fs::read(path)
+            .map_err(store::Error::from)
+            .and_then(|data| self.controller.attach(name, &data));
+
+        match attachment {
+            Ok(attachment) => self.send_note(String::new(), vec![attachment]),
+            Err(err) => self
+                .controller
+                .set_status(format!("Failed to attach {}: {err}", path.display())),
+        }
+    }
+
+    fn send_note(&mut self, msg: String, attachments: Vec<note::Attachment>) {
+        let topic = self
+            .components
+            .topics
+            .selected_topic()
+            .expect("No topic")
+            .to_string();
+        let mut note = self.controller.compose_note(topic, msg);
+        note.attachments = attachments;
+
+        let signed = match note.sign(&self.signing_key) {
+            Ok(signed) => signed,
+            Err(err) => {
+                self.controller
+                    .set_status(format!("Failed to sign note: {err}"));
+                return;
+            }
+        };
+        match self.controller.send_note(signed) {
+            Ok(()) => {}
+            Err(parts::Error::Signing(err)) => {
+                self.controller
+                    .set_status(format!("Failed to sign note parts: {err}"));
+            }
+            Err(err) => {
+                self.controller
+                    .set_status(format!("Failed to send note: {err}"));
+            }
+        }
     }
 }
 
@@ -95,29 +225,74 @@ impl ratatui::widgets::Widget for &mut App {
     where
         Self: Sized,
     {
+        let mut constraints = vec![Constraint::Length(15), Constraint::Fill(1)];
+        if self.side_pane.is_some() {
+            constraints.push(Constraint::Length(50));
+        }
+
         let layout = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints(vec![Constraint::Length(15), Constraint::Fill(1)])
+            .constraints(constraints)
             .split(area);
 
         let inner_layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Fill(1), Constraint::Length(5)])
+            .constraints(vec![
+                Constraint::Fill(1),
+                Constraint::Length(5),
+                Constraint::Length(1),
+            ])
             .split(*layout.get(1).expect("impossibru"));
 
-        self.components.topics.render(&self.controller.model(), *layout.first().expect("impossibru"), buf);
+        self.components.topics.render(
+            self.controller.model(),
+            *layout.first().expect("impossibru"),
+            buf,
+        );
 
         self.components.chat_view.render(
-            &self.controller.model(),
+            self.controller.model(),
             *inner_layout.first().expect("impossibru"),
             buf,
         );
 
         self.components.message_input.render(
-            &self.controller.model(),
+            self.controller.model(),
             *inner_layout.get(1).expect("impossibru"),
             buf,
         );
+
+        self.components.status_bar.render(
+            self.controller.model(),
+            *inner_layout.get(2).expect("impossibru"),
+            buf,
+        );
+
+        match self.side_pane {
+            Some(SidePane::Peers) => self.components.peers.render(
+                self.controller.model(),
+                *layout.get(2).expect("impossibru"),
+                buf,
+            ),
+            Some(SidePane::Blocklist) => self.components.blocklist.render(
+                self.controller.model(),
+                *layout.get(2).expect("impossibru"),
+                buf,
+            ),
+            Some(SidePane::Search) => self.components.search.render(
+                self.controller.model(),
+                *layout.get(2).expect("impossibru"),
+                buf,
+            ),
+            None => (),
+        }
+
+        // Over everything else, until it's closed.
+        if let Focus::Backup = self.focus {
+            self.components
+                .backup
+                .render(self.controller.model(), area, buf);
+        }
     }
 }
 
@@ -125,18 +300,33 @@ pub struct Components {
     topics: components::topics::Topics,
     chat_view: components::chat_view::ChatView,
     message_input: components::message_input::MessageInput,
+    status_bar: components::status_bar::StatusBar,
+    peers: components::peers::Peers,
+    blocklist: components::blocklist::Blocklist,
+    search: components::search::Search,
+    backup: components::backup::Backup,
 }
 
 impl Components {
-    fn new() -> Self {
-        let chat_view = components::chat_view::ChatView::new("Derp".to_string());
+    fn new(local_offset: time::UtcOffset) -> Self {
+        let chat_view = components::chat_view::ChatView::new("Derp".to_string(), local_offset);
         let message_input = Default::default();
         let topics = components::topics::Topics::new();
+        let status_bar = components::status_bar::StatusBar::new();
+        let peers = components::peers::Peers::new();
+        let blocklist = components::blocklist::Blocklist::new();
+        let search = components::search::Search::new(local_offset);
+        let backup = components::backup::Backup::new();
 
         Self {
             chat_view,
             message_input,
             topics,
+            status_bar,
+            peers,
+            blocklist,
+            search,
+            backup,
         }
     }
 
@@ -145,6 +335,10 @@ impl Components {
             Focus::Topics => self.topics.update(event),
             Focus::ChatView => self.chat_view.update(event),
             Focus::MessageInput => self.message_input.update(event),
+            Focus::Peers => self.peers.update(event),
+            Focus::Blocklist => self.blocklist.update(event),
+            Focus::Search => self.search.update(event),
+            Focus::Backup => self.backup.update(event),
         }
     }
 }
@@ -154,6 +348,17 @@ pub enum Focus {
     Topics,
     ChatView,
     MessageInput,
+    Peers,
+    Blocklist,
+    Search,
+    Backup,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SidePane {
+    Peers,
+    Blocklist,
+    Search,
 }
 
 use crate::components::Component as _;
@@ -163,10 +368,11 @@ use ratatui::layout::Constraint;
 use ratatui::layout::Direction;
 use ratatui::layout::Layout;
 
-use libp2p::identity;
-
+use crate::backup;
 use crate::components;
 use crate::controller;
 use crate::note;
 use crate::note::Sign;
+use crate::parts;
+use crate::store;
 use crate::tui;
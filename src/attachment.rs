@@ -0,0 +1,185 @@
+//! Content addressed file transfer.
+//!
+//! Files are split into chunks of [`CHUNK_SIZE`], each addressed by its Keccak256 hash. The note only carries the
+//! [`root`] of the chunk hashes. Whoever wants the file first asks peers for the chunk list, checks it against the root,
+//! then fetches the chunks one by one and checks each against its hash. So it doesn't matter who hands out what.
+
+pub type Hash = [u8; 32];
+
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+pub const PROTOCOL: libp2p::StreamProtocol = libp2p::StreamProtocol::new("/n2p/chunks/1.0.0");
+
+/// Enough for a chunk, or the chunk list of a file of a few GiB.
+const MAX_MESSAGE_LEN: u32 = 1024 * 1024;
+
+pub fn hash(data: &[u8]) -> Hash {
+    sha3::Keccak256::digest(data).into()
+}
+
+pub fn root(chunks: &[Hash]) -> Hash {
+    hash(&chunks.concat())
+}
+
+/// Guessed from the file name, so it's only a hint.
+pub fn mime_type(name: &str) -> String {
+    mime_guess::from_path(name)
+        .first_or_octet_stream()
+        .to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Request {
+    ChunkList(Hash),
+    Chunk(Hash),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Response {
+    ChunkList(Vec<Hash>),
+    Chunk(Vec<u8>),
+    NotFound,
+}
+
+/// Length prefixed, bincode encoded requests and responses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Codec;
+
+#[async_trait::async_trait]
+impl libp2p::request_response::Codec for Codec {
+    type Protocol = libp2p::StreamProtocol;
+    type Request = Request;
+    type Response = Response;
+
+    async fn read_request<T>(&mut self, _: &Self::Protocol, io: &mut T) -> io::Result<Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_message(io).await
+    }
+
+    async fn read_response<T>(&mut self, _: &Self::Protocol, io: &mut T) -> io::Result<Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_message(io).await
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        request: Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_message(io, &request).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        response: Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_message(io, &response).await
+    }
+}
+
+async fn read_message<T: serde::de::DeserializeOwned>(
+    io: &mut (impl AsyncRead + Unpin + Send),
+) -> io::Result<T> {
+    let mut len = [0; 4];
+    io.read_exact(&mut len).await?;
+    let len = u32::from_le_bytes(len);
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "chunk message too large",
+        ));
+    }
+
+    let mut message = vec![0; len as usize];
+    io.read_exact(&mut message).await?;
+    T::decode(message.as_slice()).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+async fn write_message(
+    io: &mut (impl AsyncWrite + Unpin + Send),
+    message: &impl serde::Serialize,
+) -> io::Result<()> {
+    let message = message
+        .encode_to_vec()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let len = u32::try_from(message.len())
+        .ok()
+        .filter(|len| *len <= MAX_MESSAGE_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "chunk message too large"))?;
+
+    io.write_all(&len.to_le_bytes()).await?;
+    io.write_all(&message).await?;
+    io.flush().await
+}
+
+use std::io;
+
+use futures::AsyncRead;
+use futures::AsyncReadExt as _;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt as _;
+use sha3::Digest as _;
+
+use crate::note::Decode as _;
+use crate::note::Encode as _;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libp2p::request_response::Codec as _;
+
+    #[test]
+    fn messages_should_survive_the_codec() {
+        futures::executor::block_on(async {
+            let chunk = vec![7; CHUNK_SIZE];
+            let mut buf = Vec::new();
+            Codec
+                .write_response(&PROTOCOL, &mut buf, Response::Chunk(chunk.clone()))
+                .await
+                .expect("failed to write response");
+            Codec
+                .write_request(&PROTOCOL, &mut buf, Request::ChunkList(hash(&chunk)))
+                .await
+                .expect("failed to write request");
+
+            let mut io = futures::io::Cursor::new(buf);
+            assert_eq!(
+                Codec.read_response(&PROTOCOL, &mut io).await.ok(),
+                Some(Response::Chunk(chunk.clone()))
+            );
+            assert_eq!(
+                Codec.read_request(&PROTOCOL, &mut io).await.ok(),
+                Some(Request::ChunkList(hash(&chunk)))
+            );
+        });
+    }
+
+    #[test]
+    fn oversized_messages_should_be_refused() {
+        let mut buf = (MAX_MESSAGE_LEN + 1).to_le_bytes().to_vec();
+        buf.extend([0; 16]);
+
+        let read = futures::executor::block_on(
+            Codec.read_response(&PROTOCOL, &mut futures::io::Cursor::new(buf)),
+        );
+
+        assert_eq!(
+            read.map_err(|err| err.kind()),
+            Err(io::ErrorKind::InvalidData)
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Backing up an identity as words to write down, and restoring it from them.
+//!
+//! The words are the BIP 39 mnemonic of the Ed25519 secret key: 24 English words, the last of which carries a
+//! checksum, so a mistyped or missing word is caught rather than restoring some other key.
+
+/// The words that restore `key_pair` with [`from_words`]. Only Ed25519 keys have them.
+pub fn to_words(key_pair: &identity::Keypair) -> Result<String, Error> {
+    let key_pair = key_pair
+        .clone()
+        .try_into_ed25519()
+        .map_err(|_| Error::UnsupportedKey)?;
+    let mnemonic = bip39::Mnemonic::from_entropy(key_pair.secret().as_ref())?;
+    Ok(mnemonic.to_string())
+}
+
+/// The key pair the words were made from. Case and the spacing between words don't matter.
+pub fn from_words(words: &str) -> Result<identity::Keypair, Error> {
+    let words = words
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mnemonic = bip39::Mnemonic::parse_normalized(&words)?;
+    let secret: [u8; 32] = mnemonic
+        .to_entropy()
+        .try_into()
+        .map_err(|_| Error::WordCount)?;
+    Ok(identity::Keypair::ed25519_from_bytes(secret).expect("Ed25519 secret keys are 32 bytes"))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid words")]
+    Mnemonic(#[from] bip39::Error),
+    #[error("expected 24 words")]
+    WordCount,
+    #[error("only Ed25519 keys can be backed up as words")]
+    UnsupportedKey,
+}
+
+use libp2p::identity;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_should_restore_the_same_key_pair() {
+        let zero = identity::Keypair::ed25519_from_bytes([0; 32]).expect("invalid key");
+        let words = to_words(&zero).expect("failed to make words");
+        assert_eq!(words, format!("{}art", "abandon ".repeat(23)));
+
+        for key_pair in [zero, identity::Keypair::generate_ed25519()] {
+            let words = to_words(&key_pair).expect("failed to make words");
+            assert_eq!(words.split(' ').count(), 24);
+
+            let restored = from_words(&words).expect("failed to restore");
+            assert_eq!(restored.public(), key_pair.public());
+            let shouted = words.to_uppercase().replace(' ', "\n ");
+            let restored = from_words(&format!("  {shouted}\n")).expect("failed to restore");
+            assert_eq!(restored.public(), key_pair.public());
+        }
+    }
+
+    #[test]
+    fn mistyped_words_should_be_caught() {
+        let abandon = "abandon ".repeat(23);
+
+        assert!(from_words(&format!("{abandon}art")).is_ok());
+        assert!(from_words(&format!("{abandon}abandon")).is_err());
+        assert!(from_words(&format!("ability {}art", "abandon ".repeat(22))).is_err());
+        assert!(from_words(&format!("{abandon}artt")).is_err());
+        assert!(from_words(&abandon).is_err());
+        // A good mnemonic, but of a shorter secret than ours.
+        assert!(matches!(
+            from_words(
+                "legal winner thank year wave sausage worth useful legal winner thank yellow"
+            ),
+            Err(Error::WordCount)
+        ));
+    }
+}
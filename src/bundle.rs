@@ -0,0 +1,204 @@
+//! Signed notes in their raw encoding, so signatures stay verifiable wherever the notes end up.
+//!
+//! A bundle starts with [`MAGIC`], followed by records. Each record is a little endian `u32` length and that many
+//! bytes holding one encoded `Signed<Note>`, compressed if that made it smaller. The note history in the store uses the same records, without the magic.
+
+pub const MAGIC: &[u8; 8] = b"n2pbndl2";
+/// Bundles from before records were compressed. Their records are all plain, so they read like any other.
+pub const PLAIN_MAGIC: &[u8; 8] = b"n2pbndl1";
+
+/// Refuse records larger than this, rather than allocating whatever a corrupt length asks for.
+const MAX_RECORD_LEN: u32 = 16 * 1024 * 1024;
+
+pub fn write<'a>(
+    mut writer: impl io::Write,
+    notes: impl IntoIterator<Item = &'a note::Signed<note::Note>>,
+) -> Result<(), Error> {
+    writer.write_all(MAGIC)?;
+    write_records(writer, notes)
+}
+
+pub fn write_records<'a>(
+    mut writer: impl io::Write,
+    notes: impl IntoIterator<Item = &'a note::Signed<note::Note>>,
+) -> Result<(), Error> {
+    for note in notes {
+        let encoded = note.encode_compressed()?;
+        let len = u32::try_from(encoded.len())
+            .ok()
+            .filter(|len| *len <= MAX_RECORD_LEN)
+            .ok_or(Error::RecordTooLarge)?;
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(&encoded)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Raw records of a bundle, left to the caller to decode, so broken notes can be counted rather than fail the bundle.
+pub fn read(mut reader: impl io::Read) -> Result<Records<impl io::Read>, Error> {
+    let mut magic = [0; MAGIC.len()];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|_| Error::NotABundle)?;
+    if &magic != MAGIC && &magic != PLAIN_MAGIC {
+        return Err(Error::NotABundle);
+    }
+
+    Ok(read_records(reader))
+}
+
+pub fn read_records<R: io::Read>(reader: R) -> Records<R> {
+    Records {
+        reader,
+        position: 0,
+    }
+}
+
+pub struct Records<R> {
+    reader: R,
+    position: u64,
+}
+
+impl<R: io::Read> Iterator for Records<R> {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut len = [0; 4];
+        match self.reader.read(&mut len[..1]) {
+            Ok(0) => return None,
+            Ok(_) => (),
+            Err(err) => return Some(Err(err.into())),
+        }
+
+        Some(self.read_record(len))
+    }
+}
+
+impl<R: io::Read> Records<R> {
+    /// How many bytes the complete records read so far take up.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn read_record(&mut self, mut len: [u8; 4]) -> Result<Vec<u8>, Error> {
+        self.reader.read_exact(&mut len[1..]).map_err(truncated)?;
+        let len = u32::from_le_bytes(len);
+        if len > MAX_RECORD_LEN {
+            return Err(Error::RecordTooLarge);
+        }
+
+        let mut record = vec![0; len as usize];
+        self.reader.read_exact(&mut record).map_err(truncated)?;
+        self.position += 4 + u64::from(len);
+
+        Ok(record)
+    }
+}
+
+fn truncated(err: io::Error) -> Error {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => Error::Truncated,
+        _ => err.into(),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("io error")]
+    Io(#[from] io::Error),
+    #[error("encoding error")]
+    Encoding(#[from] bincode::Error),
+    #[error("not a note bundle")]
+    NotABundle,
+    #[error("bundle ends in the middle of a note")]
+    Truncated,
+    #[error("note too large for a bundle")]
+    RecordTooLarge,
+}
+
+use std::io;
+
+use crate::note;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fake::Fake as _;
+    use note::Encode as _;
+    use note::Sign as _;
+    use rand::RngCore as _;
+    use rand::SeedableRng as _;
+
+    #[test]
+    fn bundles_should_keep_notes_verifiable() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut secret_key_bytes = [0; 32];
+        rng.fill_bytes(&mut secret_key_bytes);
+        let keypair = libp2p::identity::Keypair::ed25519_from_bytes(secret_key_bytes)
+            .expect("Failed to generate keypair");
+        let notes: Vec<_> = (0..3)
+            .map(|_| {
+                let note: note::Note = fake::Faker.fake_with_rng(&mut rng);
+                note.sign(&keypair).expect("Failed to sign note")
+            })
+            .collect();
+
+        let mut bundle = Vec::new();
+        write(&mut bundle, &notes).expect("failed to write bundle");
+
+        let read: Vec<_> = read(bundle.as_slice())
+            .expect("failed to read bundle")
+            .map(|record| {
+                note::Signed::<note::Note>::decode_compressed(
+                    &record.expect("failed to read record"),
+                )
+                .expect("failed to decode note")
+            })
+            .collect();
+
+        assert_eq!(read, notes);
+        assert!(read.iter().all(note::Signed::verify));
+    }
+
+    #[test]
+    fn truncated_bundles_should_be_noticed() {
+        let mut bundle = MAGIC.to_vec();
+        bundle.extend(10u32.to_le_bytes());
+        bundle.extend([0; 5]);
+
+        let records: Vec<_> = read(bundle.as_slice())
+            .expect("failed to read bundle")
+            .collect();
+
+        assert!(matches!(records.as_slice(), [Err(Error::Truncated)]));
+        assert!(matches!(read(&b"hello"[..]), Err(Error::NotABundle)));
+    }
+
+    #[test]
+    fn plain_bundles_should_still_be_read() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let keypair = libp2p::identity::Keypair::generate_ed25519();
+        let note: note::Note = fake::Faker.fake_with_rng(&mut rng);
+        let note = note.sign(&keypair).expect("Failed to sign note");
+        let plain = note.encode_to_vec().expect("failed to encode note");
+
+        let mut bundle = PLAIN_MAGIC.to_vec();
+        bundle.extend((plain.len() as u32).to_le_bytes());
+        bundle.extend(plain);
+
+        let records: Vec<_> = read(bundle.as_slice())
+            .expect("failed to read bundle")
+            .map(|record| {
+                note::Signed::<note::Note>::decode_compressed(
+                    &record.expect("failed to read record"),
+                )
+                .expect("failed to decode note")
+            })
+            .collect();
+
+        assert_eq!(records, vec![note]);
+    }
+}
@@ -0,0 +1,237 @@
+//! The bytes signatures and note IDs are made over, independent of how anything is encoded on the wire or in the
+//! store.
+//!
+//! A value is written as its fields in declaration order, where
+//!
+//! - unsigned integers are fixed width big endian,
+//! - strings and byte strings are their length as a `u32`, then their bytes,
+//! - fixed size byte arrays, like hashes, are their bytes,
+//! - sequences are their length as a `u32`, then their items,
+//! - options are `0`, or `1` and then the value,
+//! - instants are nanoseconds since the Unix epoch as an `i128`, then the UTC offset in seconds as an `i32`,
+//! - public keys are their protobuf encoding, as a byte string.
+//!
+//! What is hashed is the domain of the value as a string, then the value. The domain names the type and the version
+//! of its layout, so a signed note can never pass for a signed receipt, or anything else that is signed.
+
+/// Values with a canonical encoding, see the [module](self) docs.
+pub trait Canonical {
+    fn write_canonical(&self, out: &mut Vec<u8>);
+}
+
+/// Values that are signed on their own.
+pub trait Signable: Canonical {
+    const DOMAIN: &'static str;
+}
+
+/// The domain, then the value.
+pub fn encode(domain: &str, value: &(impl Canonical + ?Sized)) -> Vec<u8> {
+    let mut out = Vec::new();
+    domain.write_canonical(&mut out);
+    value.write_canonical(&mut out);
+    out
+}
+
+pub fn hash(domain: &str, value: &(impl Canonical + ?Sized)) -> [u8; 32] {
+    sha3::Keccak256::digest(encode(domain, value)).into()
+}
+
+/// What the signature of a value is over, before hashing.
+pub fn signing_bytes<T: Signable>(value: &T) -> Vec<u8> {
+    encode(T::DOMAIN, value)
+}
+
+fn write_len(len: usize, out: &mut Vec<u8>) {
+    u32::try_from(len)
+        .expect("canonical values are shorter than 4 GiB")
+        .write_canonical(out);
+}
+
+impl Canonical for u8 {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+
+impl Canonical for u16 {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        out.extend(self.to_be_bytes());
+    }
+}
+
+impl Canonical for u32 {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        out.extend(self.to_be_bytes());
+    }
+}
+
+impl Canonical for u64 {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        out.extend(self.to_be_bytes());
+    }
+}
+
+impl Canonical for str {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        write_len(self.len(), out);
+        out.extend(self.as_bytes());
+    }
+}
+
+impl Canonical for String {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        self.as_str().write_canonical(out);
+    }
+}
+
+impl<const N: usize> Canonical for [u8; N] {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        out.extend(self);
+    }
+}
+
+/// Byte strings are sequences of `u8`, so they are written the same way.
+impl<T: Canonical> Canonical for Vec<T> {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        write_len(self.len(), out);
+        for item in self {
+            item.write_canonical(out);
+        }
+    }
+}
+
+impl<T: Canonical> Canonical for Option<T> {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            None => 0u8.write_canonical(out),
+            Some(value) => {
+                1u8.write_canonical(out);
+                value.write_canonical(out);
+            }
+        }
+    }
+}
+
+impl Canonical for time::OffsetDateTime {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        out.extend(self.unix_timestamp_nanos().to_be_bytes());
+        out.extend(self.offset().whole_seconds().to_be_bytes());
+    }
+}
+
+impl Canonical for identity::PublicKey {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        self.encode_protobuf().write_canonical(out);
+    }
+}
+
+use libp2p::identity;
+use sha3::Digest as _;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hlc;
+    use crate::note;
+    use crate::note::Sign as _;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    fn key_pair() -> identity::Keypair {
+        identity::Keypair::ed25519_from_bytes([7; 32]).expect("invalid secret key")
+    }
+
+    fn note() -> note::Note {
+        note::Note {
+            hlc: hlc::Timestamp {
+                millis: 1_714_564_800_000,
+                counter: 1,
+            },
+            parents: vec![note::NoteId([0xaa; 32])],
+            attachments: vec![note::Attachment {
+                name: "a.txt".to_string(),
+                size: 5,
+                mime_type: "text/plain".to_string(),
+                root: [0xbb; 32],
+            }],
+            ..note::tests::note_with("ops", "hi", time::macros::datetime!(2024-05-01 12:00 UTC))
+        }
+    }
+
+    #[test]
+    fn notes_should_match_the_test_vector() {
+        let expected = [
+            "0000000b 6e32702f6e6f74652f7631",           // domain "n2p/note/v1"
+            "00000003 6f7073",                           // topic "ops"
+            "00000002 6869",                             // msg "hi"
+            "000000000000000017cb5b99f8638000 00000000", // created at, UTC
+            "0000018f34069e00 00000001",                 // hlc
+            "00000001 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", // parents
+            "00000001",                                  // attachments
+            "00000005 612e747874",                       // name "a.txt"
+            "0000000000000005",                          // size
+            "0000000a 746578742f706c61696e",             // mime type "text/plain"
+            "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb", // root
+        ]
+        .concat()
+        .replace(' ', "");
+        let note = note();
+
+        assert_eq!(hex(&signing_bytes(&note)), expected);
+        assert_eq!(
+            hex(&hash(note::Note::DOMAIN, &note)),
+            "0d9ff79762bb2ee58e7912e03855ba5ab23f1d8ec986510a37e2b1793a2c3910"
+        );
+
+        let signed = note.sign(&key_pair()).expect("failed to sign note");
+        assert_eq!(
+            hex(&signed.signature),
+            concat!(
+                "5a2be4cd09eaa0276e3eaf1f1b0920a02f840bf50b0c24fbb685fc7f9204203a",
+                "fc7665a730a5f9d8dcf816f599e4ee61233b21d3a015465aef39a506508e7d0c"
+            )
+        );
+        assert_eq!(
+            signed.id().to_string(),
+            "94c530cfcff6336ef95342e4e29bb9a1d345cb305a0d93f0029f120a8cbb1c46"
+        );
+    }
+
+    #[test]
+    fn receipts_should_match_the_test_vector() {
+        let receipt = note::Receipt {
+            note_id: note::NoteId([0x11; 32]),
+        };
+
+        assert_eq!(
+            hex(&signing_bytes(&receipt)),
+            ["0000000e", "6e32702f726563656970742f7631", &"11".repeat(32)].concat()
+        );
+    }
+
+    #[test]
+    fn signatures_should_not_pass_for_another_type() {
+        // A receipt's signature, put on a part of the same note.
+        let receipt = note::Receipt {
+            note_id: note::NoteId([0x11; 32]),
+        }
+        .sign(&key_pair())
+        .expect("failed to sign receipt");
+        let part = note::Signed {
+            inner: note::Part {
+                note_id: note::NoteId([0x11; 32]),
+                index: 0,
+                count: 0,
+                data: Vec::new(),
+            },
+            pub_key: receipt.pub_key.clone(),
+            signature: receipt.signature.clone(),
+        };
+
+        assert!(receipt.verify());
+        assert!(!part.verify());
+    }
+}
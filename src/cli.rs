@@ -0,0 +1,257 @@
+#[derive(Debug, clap::Parser)]
+#[command(version, about = "Notes to peer")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Don't look for peers on the local network
+    #[arg(long)]
+    pub no_mdns: bool,
+
+    /// DHT bootstrap peer, e.g. /ip4/198.51.100.1/tcp/4001/p2p/<peer id>
+    #[arg(long = "bootstrap")]
+    pub bootstrap_peers: Vec<libp2p::Multiaddr>,
+
+    /// Relay to keep a reservation on, so peers behind NAT can reach us
+    #[arg(long = "relay")]
+    pub relays: Vec<libp2p::Multiaddr>,
+
+    /// Don't tell authors that we received their notes
+    #[arg(long)]
+    pub no_receipts: bool,
+
+    /// Command to run when someone mentions you. Gets the note in N2P_TOPIC, N2P_AUTHOR and N2P_MESSAGE.
+    #[arg(long)]
+    pub notify_command: Option<std::path::PathBuf>,
+
+    /// Swarm key file of a private network. Only peers with the same key can connect.
+    #[arg(long, global = true)]
+    pub swarm_key: Option<std::path::PathBuf>,
+
+    /// Where to keep notes and other state across restarts [default: the platform's data directory]
+    #[arg(long, global = true)]
+    pub data_dir: Option<std::path::PathBuf>,
+
+    /// Sign with this OpenSSH Ed25519 private key instead of our own identity. Asks for its passphrase if needed.
+    #[arg(long, global = true, conflicts_with = "ssh_agent")]
+    pub ssh_key: Option<std::path::PathBuf>,
+
+    /// Sign with an Ed25519 key held by the SSH agent at SSH_AUTH_SOCK
+    #[arg(long, global = true)]
+    pub ssh_agent: bool,
+
+    /// Fingerprint of the agent's key to sign with, if it holds several [default: the first Ed25519 key]
+    #[arg(
+        long,
+        global = true,
+        value_name = "FINGERPRINT",
+        requires = "ssh_agent"
+    )]
+    pub ssh_agent_key: Option<String>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Run a relay server for peers that can't reach each other directly
+    Relay {
+        /// Address to listen on
+        #[arg(long = "listen", default_values = ["/ip4/0.0.0.0/udp/4001/quic-v1", "/ip4/0.0.0.0/tcp/4001"])]
+        listen_addrs: Vec<libp2p::Multiaddr>,
+
+        /// Publicly reachable address of this relay. Defaults to the listen addresses.
+        #[arg(long = "external-addr")]
+        external_addrs: Vec<libp2p::Multiaddr>,
+    },
+
+    /// Write stored notes to a file, for archiving or sharing with people who don't run n2p
+    Export {
+        /// Only export this topic
+        #[arg(long)]
+        topic: Option<String>,
+
+        #[arg(long, value_enum, default_value_t = export::Format::Markdown)]
+        format: export::Format,
+
+        /// Where to write the export [default: standard output]
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Merge notes from a bundle written by `export --format bundle`, checking every signature
+    Import {
+        bundle: std::path::PathBuf,
+
+        /// Also publish the imported notes, next time we're online
+        #[arg(long)]
+        republish: bool,
+    },
+
+    /// Set the profile peers see when they haven't named you themselves
+    Profile {
+        /// Name you'd like to be shown as
+        #[arg(long = "name")]
+        display_name: String,
+
+        /// What you're up to
+        #[arg(long, default_value = "")]
+        status: String,
+
+        /// Small image that represents you. Only its hash is published.
+        #[arg(long)]
+        avatar: Option<std::path::PathBuf>,
+    },
+
+    /// Show the key we sign with, and its SSH fingerprint to compare with people out of band
+    Whoami,
+
+    /// Switch to a new identity key, vouched for by the key we sign with now. Contacts follow to the new key.
+    Rotate,
+
+    /// Withdraw trust in the key we sign with, e.g. when retiring a device, or ahead of time in case it gets lost
+    Revoke {
+        /// When the key stopped being safe, in RFC 3339 [default: now]
+        #[arg(long, value_parser = parse_time)]
+        since: Option<time::OffsetDateTime>,
+
+        /// Why, for whoever wonders
+        #[arg(long, default_value = "")]
+        reason: String,
+
+        /// Write the certificate here to keep somewhere safe and publish later, instead of publishing it now
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Agree to be a device of the identity, as `whoami` shows it there. Hand the file to `device` on that machine.
+    Join {
+        identity: note::PubKey,
+
+        /// Where to write the agreement
+        #[arg(long, short)]
+        output: std::path::PathBuf,
+    },
+
+    /// Vouch for another device of yours, from the agreement `join` wrote there, so its notes show up as yours
+    Device {
+        membership: std::path::PathBuf,
+
+        /// What you call the device, like "laptop"
+        #[arg(long)]
+        name: String,
+
+        /// Write the certificate here to publish from the device, instead of publishing it from here
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Publish a certificate written by `revoke --output` or `device --output`, next time we're online
+    Publish { certificate: std::path::PathBuf },
+
+    /// Show the words that restore the key we sign with, to write down and keep somewhere safe
+    Backup,
+
+    /// Restore our identity from the words `backup` showed, typed in or piped, so they stay out of the shell history
+    Restore {
+        /// Replace the identity we have now, which is gone for good unless it's backed up too
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl Cli {
+    pub fn controller_config(&self) -> anyhow::Result<controller::Config> {
+        let swarm_key = self.swarm_key()?;
+        let mut listen_addrs = controller::Config::default().listen_addrs;
+        if swarm_key.is_some() {
+            listen_addrs.retain(|addr| !is_quic(addr));
+        }
+
+        let data_dir = self.data_dir();
+        let signing_key = self.signing_key()?;
+
+        Ok(controller::Config {
+            mdns: !self.no_mdns,
+            bootstrap_peers: self.bootstrap_peers.clone(),
+            relays: self.relays.clone(),
+            swarm_key,
+            data_dir,
+            signing_key,
+            send_receipts: !self.no_receipts,
+            notify_hook: self
+                .notify_command
+                .clone()
+                .map(|command| notify::Hook { command }),
+            listen_addrs,
+            ..Default::default()
+        })
+    }
+
+    /// The SSH key or agent we were asked to use, or else our own identity.
+    pub fn signing_key(&self) -> anyhow::Result<note::SigningKey> {
+        if let Some(path) = &self.ssh_key {
+            let key_pair = ssh::read_key(path, || {
+                rpassword::prompt_password(format!("Passphrase for {}: ", path.display()))
+            })
+            .with_context(|| format!("failed to read SSH key from {}", path.display()))?;
+            return Ok(key_pair.into());
+        }
+
+        if self.ssh_agent {
+            let socket = std::env::var_os("SSH_AUTH_SOCK").context("SSH_AUTH_SOCK isn't set")?;
+            let agent = ssh::Agent::connect(socket, self.ssh_agent_key.as_deref())
+                .context("failed to get a key from the SSH agent")?;
+            return Ok(note::SigningKey::Agent(agent));
+        }
+
+        Ok(self
+            .store()?
+            .load_or_create_identity()
+            .context("failed to load identity")?
+            .into())
+    }
+
+    pub fn store(&self) -> anyhow::Result<store::Store> {
+        match self.data_dir() {
+            Some(dir) => store::Store::open(&dir)
+                .with_context(|| format!("failed to open data directory {}", dir.display())),
+            None => Ok(store::Store::in_memory()),
+        }
+    }
+
+    pub fn data_dir(&self) -> Option<std::path::PathBuf> {
+        self.data_dir
+            .clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join("n2p")))
+    }
+
+    pub fn swarm_key(&self) -> anyhow::Result<Option<libp2p::pnet::PreSharedKey>> {
+        let Some(path) = &self.swarm_key else {
+            return Ok(None);
+        };
+
+        let swarm_key = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read swarm key from {}", path.display()))?
+            .parse()
+            .with_context(|| format!("invalid swarm key in {}", path.display()))?;
+
+        Ok(Some(swarm_key))
+    }
+}
+
+fn parse_time(time: &str) -> Result<time::OffsetDateTime, time::error::Parse> {
+    time::OffsetDateTime::parse(time, &time::format_description::well_known::Rfc3339)
+}
+
+pub fn is_quic(addr: &libp2p::Multiaddr) -> bool {
+    addr.iter()
+        .any(|protocol| matches!(protocol, libp2p::multiaddr::Protocol::QuicV1))
+}
+
+use anyhow::Context;
+
+use crate::controller;
+use crate::export;
+use crate::note;
+use crate::notify;
+use crate::ssh;
+use crate::store;
@@ -10,13 +10,42 @@ pub trait Component {
 
 pub enum Effect {
     SendMessage(String),
+    /// Send the file at the path in a note of its own.
+    SendAttachment(std::path::PathBuf),
     ViewTopic(String),
+    JumpToNote(String, note::NoteId),
+    UpdateContact(note::PubKey, ContactUpdate),
+    UpdateBlocks(BlocksUpdate),
+    SaveAttachments(Vec<note::Attachment>),
+    /// Restore our identity from the words of a backup, replacing the one we have if asked to.
+    RestoreIdentity(String, bool),
     Return,
     Nothing,
 }
 
+pub enum ContactUpdate {
+    Petname(String),
+    Notes(String),
+    CycleTrust,
+}
+
+pub enum BlocksUpdate {
+    BlockAuthor(note::PubKey),
+    UnblockAuthor(note::PubKey),
+    BlockPeer(libp2p::PeerId),
+    UnblockPeer(libp2p::PeerId),
+    Mute(String, note::PubKey),
+    Unmute(String, note::PubKey),
+}
+
+pub mod backup;
+pub mod blocklist;
 pub mod chat_view;
 pub mod message_input;
+pub mod peers;
+pub mod search;
+pub mod status_bar;
 pub mod topics;
 
 use crate::model;
+use crate::note;
@@ -0,0 +1,164 @@
+/// Shows the words that restore the key we sign with, and takes words to restore an identity from.
+pub struct Backup {
+    /// Our key's words, or why there are none.
+    words: Result<String, String>,
+    /// The words stay hidden until asked for, anyone looking can sign as us with them.
+    revealed: bool,
+    text_area: tui_textarea::TextArea<'static>,
+    /// Restoring from the words entered replaces the identity we have, see [`Backup::confirm`].
+    replace: bool,
+}
+
+impl Backup {
+    pub fn new() -> Self {
+        Self {
+            words: Err(String::new()),
+            revealed: false,
+            text_area: Self::text_area(),
+            replace: false,
+        }
+    }
+
+    fn text_area() -> tui_textarea::TextArea<'static> {
+        let mut text_area = tui_textarea::TextArea::default();
+        text_area.set_mask_char('•');
+        text_area.set_placeholder_text("Words to restore from, then Enter");
+        text_area
+    }
+
+    pub fn open(&mut self, signing_key: &note::SigningKey) {
+        self.words = match signing_key {
+            note::SigningKey::KeyPair(key_pair) => {
+                backup::to_words(key_pair).map_err(|err| err.to_string())
+            }
+            note::SigningKey::Agent(_) => {
+                Err("Keys in the SSH agent can't be backed up as words.".to_string())
+            }
+        };
+        self.revealed = false;
+        self.text_area = Self::text_area();
+        self.replace = false;
+    }
+
+    /// We have another identity. Enter again replaces it, unless the words change first.
+    pub fn confirm(&mut self) {
+        self.replace = true;
+    }
+
+    pub fn restored(&mut self) {
+        self.text_area = Self::text_area();
+        self.revealed = false;
+        self.replace = false;
+    }
+}
+
+impl Default for Backup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl components::Component for Backup {
+    fn update(&mut self, event: crossterm::event::KeyEvent) -> components::Effect {
+        match (event.modifiers, event.code) {
+            (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('r')) => {
+                self.revealed = !self.revealed;
+            }
+            (_, crossterm::event::KeyCode::Enter) => {
+                let words = self.text_area.lines().join(" ");
+                return components::Effect::RestoreIdentity(words, self.replace);
+            }
+            (_, crossterm::event::KeyCode::Esc) => {
+                self.revealed = false;
+                return components::Effect::Return;
+            }
+            _ => {
+                if self.text_area.input(event) {
+                    self.replace = false;
+                }
+            }
+        }
+
+        components::Effect::Nothing
+    }
+
+    fn render(
+        &mut self,
+        _model: &model::Model,
+        area: ratatui::layout::Rect,
+        buf: &mut ratatui::buffer::Buffer,
+    ) {
+        let popup = ratatui::layout::Rect {
+            x: area.x + area.width.saturating_sub(WIDTH) / 2,
+            y: area.y + area.height.saturating_sub(HEIGHT) / 2,
+            width: WIDTH.min(area.width),
+            height: HEIGHT.min(area.height),
+        };
+        ratatui::widgets::Clear.render(popup, buf);
+
+        let block = ratatui::widgets::Block::bordered()
+            .border_set(ratatui::symbols::border::THICK)
+            .title("Backup · Ctrl+R shows the words · Esc closes");
+        let inner = block.inner(popup);
+        block.render(popup, buf);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Fill(1), Constraint::Length(3)])
+            .split(inner);
+
+        let mut lines: Vec<_> = match &self.words {
+            Ok(words) if self.revealed => words
+                .split(' ')
+                .collect::<Vec<_>>()
+                .chunks(WORDS_PER_LINE)
+                .enumerate()
+                .map(|(line, words)| {
+                    let words: String = words
+                        .iter()
+                        .enumerate()
+                        .map(|(i, word)| {
+                            format!("{:>2}. {word:<10}", line * WORDS_PER_LINE + i + 1)
+                        })
+                        .collect();
+                    ratatui::text::Line::from(words)
+                })
+                .collect(),
+            Ok(_) => vec![
+                ratatui::text::Line::from("24 words restore the key you sign with."),
+                ratatui::text::Line::from(
+                    "Anyone who sees them can sign as you, so check nobody's looking.",
+                ),
+            ],
+            Err(err) => vec![ratatui::text::Line::from(err.as_str())],
+        };
+        lines.push(ratatui::text::Line::from(""));
+        lines.push(ratatui::text::Line::from(
+            "Restoring takes effect when n2p starts again. Another identity we have is asked about first.",
+        ));
+        ratatui::widgets::Paragraph::new(lines).render(layout[0], buf);
+
+        let title = if self.replace {
+            "Restore · Enter again replaces the identity we have"
+        } else {
+            "Restore"
+        };
+        self.text_area
+            .set_block(ratatui::widgets::Block::bordered().title(title));
+        self.text_area.widget().render(layout[1], buf);
+    }
+}
+
+const WIDTH: u16 = 88;
+const HEIGHT: u16 = 11;
+const WORDS_PER_LINE: usize = 6;
+
+use ratatui::layout::Constraint;
+use ratatui::layout::Direction;
+use ratatui::layout::Layout;
+use ratatui::widgets::Widget;
+
+use crate::backup;
+use crate::components;
+use crate::model;
+use crate::note;
@@ -0,0 +1,80 @@
+/// Everyone we blocked or muted, so they can be let back in.
+#[derive(Default)]
+pub struct Blocklist {
+    list_state: ratatui::widgets::ListState,
+    /// What undoes each listed entry, in list order.
+    undo: Vec<components::BlocksUpdate>,
+}
+
+impl Blocklist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl components::Component for Blocklist {
+    fn update(&mut self, event: crossterm::event::KeyEvent) -> components::Effect {
+        match event.code {
+            crossterm::event::KeyCode::Up => self.list_state.select_previous(),
+            crossterm::event::KeyCode::Down => self.list_state.select_next(),
+            crossterm::event::KeyCode::Enter => return components::Effect::Return,
+            crossterm::event::KeyCode::Char('u') | crossterm::event::KeyCode::Delete => {
+                if let Some(selected) = self.list_state.selected() {
+                    if selected < self.undo.len() {
+                        return components::Effect::UpdateBlocks(self.undo.remove(selected));
+                    }
+                }
+            }
+            _ => (),
+        };
+
+        components::Effect::Nothing
+    }
+
+    fn render(
+        &mut self,
+        model: &model::Model,
+        area: ratatui::layout::Rect,
+        buf: &mut ratatui::buffer::Buffer,
+    ) {
+        let block = ratatui::widgets::Block::bordered()
+            .border_set(ratatui::symbols::border::THICK)
+            .title("Blocked (u to undo)");
+
+        let mut items = Vec::new();
+        self.undo.clear();
+
+        for pub_key in &model.blocks.authors {
+            items.push(format!("author {}", model.author_name(pub_key)));
+            self.undo
+                .push(components::BlocksUpdate::UnblockAuthor(pub_key.clone()));
+        }
+        for peer_id in &model.blocks.peers {
+            items.push(format!("peer {}", peers::short_peer_id(peer_id)));
+            self.undo
+                .push(components::BlocksUpdate::UnblockPeer(*peer_id));
+        }
+        for (topic, muted) in &model.blocks.mutes {
+            for pub_key in muted {
+                items.push(format!("muted {} in {topic}", model.author_name(pub_key)));
+                self.undo.push(components::BlocksUpdate::Unmute(
+                    topic.clone(),
+                    pub_key.clone(),
+                ));
+            }
+        }
+
+        let list = ratatui::widgets::List::new(items)
+            .block(block)
+            .style(ratatui::style::Style::default().fg(ratatui::style::Color::White))
+            .highlight_style(
+                ratatui::style::Style::default().add_modifier(ratatui::style::Modifier::BOLD),
+            );
+
+        ratatui::widgets::StatefulWidget::render(list, area, buf, &mut self.list_state);
+    }
+}
+
+use crate::components;
+use crate::components::peers;
+use crate::model;
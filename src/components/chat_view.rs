@@ -1,23 +1,172 @@
 pub struct ChatView {
     topic: String,
+    local_offset: time::UtcOffset,
     list_state: ratatui::widgets::ListState,
+    /// Authors of the listed notes and what we know about them, in list order. Devices are listed as their identity.
+    authors: Vec<(note::PubKey, model::Contact)>,
+    /// Keys that signed the listed notes, in list order.
+    signers: Vec<note::PubKey>,
+    /// Attachments of the listed notes, in list order.
+    attachments: Vec<Vec<note::Attachment>>,
+    /// Keep the newest note selected until the user picks another one.
+    follow: bool,
+    /// Note to select once it's listed.
+    jump_to: Option<note::NoteId>,
+    editing: Option<Editing>,
+}
+
+struct Editing {
+    pub_key: note::PubKey,
+    field: ContactField,
+    text_area: tui_textarea::TextArea<'static>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ContactField {
+    Petname,
+    Notes,
 }
 
 impl ChatView {
-    pub fn new(topic: String) -> Self {
+    pub fn new(topic: String, local_offset: time::UtcOffset) -> Self {
         Self {
             topic,
+            local_offset,
             list_state: Default::default(),
+            authors: Vec::new(),
+            signers: Vec::new(),
+            attachments: Vec::new(),
+            follow: true,
+            jump_to: None,
+            editing: None,
         }
     }
 
     pub fn view(&mut self, topic: String) {
-        self.topic = topic
+        self.topic = topic;
+        self.follow = true;
+    }
+
+    /// The topic, while its latest notes are on screen.
+    pub fn topic_read(&self) -> Option<&str> {
+        self.follow.then_some(self.topic.as_str())
+    }
+
+    pub fn jump_to(&mut self, id: note::NoteId) {
+        self.follow = false;
+        self.jump_to = Some(id);
+    }
+
+    fn selected_author(&self) -> Option<&(note::PubKey, model::Contact)> {
+        self.list_state
+            .selected()
+            .and_then(|selected| self.authors.get(selected))
+    }
+
+    fn edit(&mut self, field: ContactField) {
+        let Some((pub_key, contact)) = self.selected_author() else {
+            return;
+        };
+
+        let (title, text) = match field {
+            ContactField::Petname => ("Petname", &contact.petname),
+            ContactField::Notes => ("Notes", &contact.notes),
+        };
+        let mut text_area = tui_textarea::TextArea::new(vec![text.clone()]);
+        text_area.set_block(
+            ratatui::widgets::Block::bordered().title(format!("{title} for {}", pub_key.short())),
+        );
+        text_area.move_cursor(tui_textarea::CursorMove::End);
+
+        self.editing = Some(Editing {
+            pub_key: pub_key.clone(),
+            field,
+            text_area,
+        });
+    }
+
+    fn update_editing(&mut self, event: crossterm::event::KeyEvent) -> components::Effect {
+        let Some(editing) = &mut self.editing else {
+            return components::Effect::Nothing;
+        };
+
+        match event.code {
+            crossterm::event::KeyCode::Enter => {
+                let editing = self.editing.take().expect("editing");
+                let text = editing.text_area.lines().join(" ").trim().to_string();
+                let update = match editing.field {
+                    ContactField::Petname => components::ContactUpdate::Petname(text),
+                    ContactField::Notes => components::ContactUpdate::Notes(text),
+                };
+                components::Effect::UpdateContact(editing.pub_key, update)
+            }
+            crossterm::event::KeyCode::Esc => {
+                self.editing = None;
+                components::Effect::Nothing
+            }
+            _ => {
+                editing.text_area.input(event);
+                components::Effect::Nothing
+            }
+        }
     }
 }
 
 impl components::Component for ChatView {
-    fn update(&mut self, _event: crossterm::event::KeyEvent) -> components::Effect {
+    fn update(&mut self, event: crossterm::event::KeyEvent) -> components::Effect {
+        if self.editing.is_some() {
+            return self.update_editing(event);
+        }
+
+        match event.code {
+            crossterm::event::KeyCode::Up => {
+                self.follow = false;
+                self.list_state.select_previous();
+            }
+            crossterm::event::KeyCode::Down => {
+                self.follow = false;
+                self.list_state.select_next();
+            }
+            crossterm::event::KeyCode::End => self.follow = true,
+            crossterm::event::KeyCode::Char('c') => self.edit(ContactField::Petname),
+            crossterm::event::KeyCode::Char('n') => self.edit(ContactField::Notes),
+            crossterm::event::KeyCode::Char('t') => {
+                if let Some((pub_key, _)) = self.selected_author() {
+                    return components::Effect::UpdateContact(
+                        pub_key.clone(),
+                        components::ContactUpdate::CycleTrust,
+                    );
+                }
+            }
+            crossterm::event::KeyCode::Char('b') => {
+                if let Some((pub_key, _)) = self.selected_author() {
+                    return components::Effect::UpdateBlocks(
+                        components::BlocksUpdate::BlockAuthor(pub_key.clone()),
+                    );
+                }
+            }
+            crossterm::event::KeyCode::Char('m') => {
+                if let Some((pub_key, _)) = self.selected_author() {
+                    return components::Effect::UpdateBlocks(components::BlocksUpdate::Mute(
+                        self.topic.clone(),
+                        pub_key.clone(),
+                    ));
+                }
+            }
+            crossterm::event::KeyCode::Char('s') => {
+                if let Some(attachments) = self
+                    .list_state
+                    .selected()
+                    .and_then(|selected| self.attachments.get(selected))
+                    .filter(|attachments| !attachments.is_empty())
+                {
+                    return components::Effect::SaveAttachments(attachments.clone());
+                }
+            }
+            crossterm::event::KeyCode::Esc => return components::Effect::Return,
+            _ => (),
+        }
+
         components::Effect::Nothing
     }
 
@@ -27,18 +176,104 @@ impl components::Component for ChatView {
         area: ratatui::layout::Rect,
         buf: &mut ratatui::buffer::Buffer,
     ) {
-        let block = ratatui::widgets::Block::bordered().border_set(ratatui::symbols::border::THICK);
-
-        let items: Vec<_> = model
-            .topics
-            .get(&self.topic)
-            .cloned()
-            .unwrap_or_default()
-            .notes
-            .values()
-            .map(|note| note.inner.msg.clone())
+        let topic = model.topics.get(&self.topic).cloned().unwrap_or_default();
+        let notes: Vec<_> = topic
+            .ordered()
+            .into_iter()
+            .filter(|note| !model.is_muted(&self.topic, &note.pub_key))
+            .collect();
+
+        self.authors = notes
+            .iter()
+            .map(|note| {
+                let identity = model.keys.identity(&note.pub_key);
+                let contact = model.contacts.get(identity).cloned().unwrap_or_default();
+                (identity.clone(), contact)
+            })
+            .collect();
+        self.signers = notes.iter().map(|note| note.pub_key.clone()).collect();
+        self.attachments = notes
+            .iter()
+            .map(|note| note.inner.attachments.clone())
             .collect();
 
+        let items: Vec<_> = notes
+            .iter()
+            .map(|note| {
+                let marker = match model.delivery(&note.id()) {
+                    Some(model::Delivery::Pending) => "⧗ ",
+                    Some(model::Delivery::Delivered) => "✓ ",
+                    None => "",
+                };
+                let seen_by = match model.seen_by(&note.id()) {
+                    0 => String::new(),
+                    n => format!(" · seen by {n}"),
+                };
+                let attachments: String = note
+                    .inner
+                    .attachments
+                    .iter()
+                    .map(|attachment| describe_attachment(model, attachment))
+                    .collect();
+                let author = model.author_name(&note.pub_key);
+                let revoked = if model.is_revoked(note) {
+                    " (revoked key)"
+                } else {
+                    ""
+                };
+                let created_at = note.inner.created_at.to_offset(self.local_offset);
+                format!(
+                    "{:02}:{:02} {marker}{author}{revoked}: {}{attachments}{seen_by}",
+                    created_at.hour(),
+                    created_at.minute(),
+                    note.inner.msg
+                )
+            })
+            .collect();
+
+        if let Some(id) = self.jump_to.take() {
+            if let Some(position) = notes.iter().position(|note| note.id() == id) {
+                self.list_state.select(Some(position));
+            }
+        }
+        if self.follow {
+            self.list_state.select_last();
+        }
+
+        let mut block =
+            ratatui::widgets::Block::bordered().border_set(ratatui::symbols::border::THICK);
+        if let Some((pub_key, contact)) = self.selected_author() {
+            let mut title = format!("{} · {}", model.author_name(pub_key), contact.trust);
+            if let Some(fingerprint) = pub_key.ssh_fingerprint() {
+                title.push_str(&format!(" · {fingerprint}"));
+            }
+            if let Some(device) = self
+                .list_state
+                .selected()
+                .and_then(|selected| self.signers.get(selected))
+                .and_then(|signer| model.keys.device(signer))
+            {
+                title.push_str(&format!(" · on {}", device.inner.name));
+            }
+            if let Some(revoked_at) = model.keys.revoked_at(pub_key) {
+                let revoked_at = revoked_at.to_offset(self.local_offset).date();
+                title.push_str(&format!(" · revoked since {revoked_at}"));
+            }
+            let current = model.keys.current(pub_key);
+            if current != pub_key {
+                title.push_str(&format!(" · now {}", model.author_name(current)));
+            }
+            if !contact.notes.is_empty() {
+                title.push_str(&format!(" · {}", contact.notes));
+            }
+            if let Some(profile) = model.profile(pub_key) {
+                if !profile.inner.status.is_empty() {
+                    title.push_str(&format!(" · \"{}\"", profile.inner.status));
+                }
+            }
+            block = block.title(title);
+        }
+
         let list = ratatui::widgets::List::new(items)
             .block(block)
             .style(ratatui::style::Style::default().fg(ratatui::style::Color::White))
@@ -46,10 +281,41 @@ impl components::Component for ChatView {
                 ratatui::style::Style::default().add_modifier(ratatui::style::Modifier::ITALIC),
             );
 
-        self.list_state.select_last();
-
         ratatui::widgets::StatefulWidget::render(list, area, buf, &mut self.list_state);
+
+        if let Some(editing) = &self.editing {
+            let popup = ratatui::layout::Rect {
+                x: area.x + 2,
+                y: area.y + area.height.saturating_sub(4),
+                width: area.width.saturating_sub(4),
+                height: 3.min(area.height),
+            };
+            ratatui::widgets::Widget::render(ratatui::widgets::Clear, popup, buf);
+            ratatui::widgets::Widget::render(editing.text_area.widget(), popup, buf);
+        }
     }
 }
 
+/// Name, size and how far along fetching it is.
+fn describe_attachment(model: &model::Model, attachment: &note::Attachment) -> String {
+    let size = match attachment.size {
+        size if size < 1024 => format!("{size} B"),
+        size if size < 1024 * 1024 => format!("{} KiB", size / 1024),
+        size => format!("{} MiB", size / (1024 * 1024)),
+    };
+    let transfer = match model.transfers.get(&attachment.root) {
+        Some(model::Transfer::Fetching { total: 0, .. }) => " · fetching".to_string(),
+        Some(model::Transfer::Fetching { received, total }) => {
+            format!(" · fetching {received}/{total}")
+        }
+        Some(model::Transfer::Saved(_)) => " · saved".to_string(),
+        Some(model::Transfer::Failed) => " · failed".to_string(),
+        None => String::new(),
+    };
+
+    format!(" [📎 {} · {size}{transfer}]", attachment.name)
+}
+
 use crate::components;
+use crate::model;
+use crate::note;
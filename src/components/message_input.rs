@@ -21,6 +21,10 @@ impl components::Component for MessageInput {
             (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('s')) => {
                 components::Effect::SendMessage(self.get_message())
             }
+            // What was typed is the path of the file to send.
+            (crossterm::event::KeyModifiers::CONTROL, crossterm::event::KeyCode::Char('o')) => {
+                components::Effect::SendAttachment(self.get_message().trim().into())
+            }
             _ => {
                 self.text_area.input(event);
                 components::Effect::Nothing
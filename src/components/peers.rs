@@ -0,0 +1,136 @@
+#[derive(Default)]
+pub struct Peers {
+    list_state: ratatui::widgets::ListState,
+    /// Listed peers in list order.
+    peer_ids: Vec<(libp2p::PeerId, bool)>,
+}
+
+impl Peers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl components::Component for Peers {
+    fn update(&mut self, event: crossterm::event::KeyEvent) -> components::Effect {
+        match event.code {
+            crossterm::event::KeyCode::Up => self.list_state.select_previous(),
+            crossterm::event::KeyCode::Down => self.list_state.select_next(),
+            crossterm::event::KeyCode::Enter => return components::Effect::Return,
+            crossterm::event::KeyCode::Char('b') => {
+                let selected = self
+                    .list_state
+                    .selected()
+                    .and_then(|selected| self.peer_ids.get(selected));
+                if let Some(&(peer_id, blocked)) = selected {
+                    return components::Effect::UpdateBlocks(if blocked {
+                        components::BlocksUpdate::UnblockPeer(peer_id)
+                    } else {
+                        components::BlocksUpdate::BlockPeer(peer_id)
+                    });
+                }
+            }
+            _ => (),
+        };
+
+        components::Effect::Nothing
+    }
+
+    fn render(
+        &mut self,
+        model: &model::Model,
+        area: ratatui::layout::Rect,
+        buf: &mut ratatui::buffer::Buffer,
+    ) {
+        let block = ratatui::widgets::Block::bordered()
+            .border_set(ratatui::symbols::border::THICK)
+            .title(format!(
+                "Peers ({} connected)",
+                model
+                    .peers
+                    .values()
+                    .filter(|peer| peer.is_connected())
+                    .count()
+            ));
+
+        self.peer_ids = model
+            .peers
+            .keys()
+            .map(|peer_id| (*peer_id, model.blocks.peers.contains(peer_id)))
+            .collect();
+
+        let items: Vec<_> = model
+            .peers
+            .iter()
+            .map(|(peer_id, peer)| peer_item(peer_id, peer, model.blocks.peers.contains(peer_id)))
+            .collect();
+
+        let list = ratatui::widgets::List::new(items)
+            .block(block)
+            .style(ratatui::style::Style::default().fg(ratatui::style::Color::White))
+            .highlight_style(
+                ratatui::style::Style::default().add_modifier(ratatui::style::Modifier::BOLD),
+            );
+
+        ratatui::widgets::StatefulWidget::render(list, area, buf, &mut self.list_state);
+    }
+}
+
+fn peer_item(
+    peer_id: &libp2p::PeerId,
+    peer: &model::Peer,
+    blocked: bool,
+) -> ratatui::widgets::ListItem<'static> {
+    let mut lines = Vec::new();
+
+    let summary = match (&peer.connected_since, &peer.transport) {
+        _ if blocked => format!("{} blocked", short_peer_id(peer_id)),
+        (Some(since), Some(transport)) => format!(
+            "{} {transport} since {:02}:{:02}",
+            short_peer_id(peer_id),
+            since.hour(),
+            since.minute()
+        ),
+        _ => format!("{} not connected", short_peer_id(peer_id)),
+    };
+    let rtt = peer
+        .rtt
+        .map(|rtt| format!(" {}ms", rtt.as_millis()))
+        .unwrap_or_default();
+    lines.push(ratatui::text::Line::from(summary + &rtt).bold());
+
+    if let Some(agent_version) = &peer.agent_version {
+        lines.push(ratatui::text::Line::from(format!("  {agent_version}")));
+    }
+
+    if !peer.topics.is_empty() {
+        let topics: Vec<_> = peer.topics.iter().map(String::as_str).collect();
+        lines.push(ratatui::text::Line::from(format!(
+            "  topics: {}",
+            topics.join(", ")
+        )));
+    }
+
+    for addr in &peer.addresses {
+        lines.push(ratatui::text::Line::from(format!("  {addr}")).dark_gray());
+    }
+
+    let item = ratatui::widgets::ListItem::new(lines);
+    if peer.is_connected() {
+        item
+    } else {
+        item.dark_gray()
+    }
+}
+
+/// Peer IDs all start the same, so the tail is what tells them apart.
+pub fn short_peer_id(peer_id: &libp2p::PeerId) -> String {
+    let peer_id = peer_id.to_base58();
+    let tail = peer_id.len().saturating_sub(8);
+    format!("…{}", &peer_id[tail..])
+}
+
+use ratatui::style::Stylize;
+
+use crate::components;
+use crate::model;
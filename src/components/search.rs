@@ -0,0 +1,119 @@
+pub struct Search {
+    local_offset: time::UtcOffset,
+    text_area: tui_textarea::TextArea<'static>,
+    list_state: ratatui::widgets::ListState,
+    /// Where the listed notes are, in list order.
+    results: Vec<(String, note::NoteId)>,
+}
+
+impl Search {
+    pub fn new(local_offset: time::UtcOffset) -> Self {
+        let mut text_area = tui_textarea::TextArea::default();
+        text_area.set_placeholder_text("words topic: from: before:YYYY-MM-DD after:YYYY-MM-DD");
+
+        Self {
+            local_offset,
+            text_area,
+            list_state: Default::default(),
+            results: Vec::new(),
+        }
+    }
+}
+
+impl components::Component for Search {
+    fn update(&mut self, event: crossterm::event::KeyEvent) -> components::Effect {
+        match event.code {
+            crossterm::event::KeyCode::Up => self.list_state.select_previous(),
+            crossterm::event::KeyCode::Down => self.list_state.select_next(),
+            crossterm::event::KeyCode::Enter => {
+                let selected = self
+                    .list_state
+                    .selected()
+                    .and_then(|selected| self.results.get(selected));
+                if let Some((topic, id)) = selected {
+                    return components::Effect::JumpToNote(topic.clone(), *id);
+                }
+            }
+            crossterm::event::KeyCode::Esc => return components::Effect::Return,
+            _ => {
+                self.text_area.input(event);
+                self.list_state.select(None);
+            }
+        };
+
+        components::Effect::Nothing
+    }
+
+    fn render(
+        &mut self,
+        model: &model::Model,
+        area: ratatui::layout::Rect,
+        buf: &mut ratatui::buffer::Buffer,
+    ) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Fill(1)])
+            .split(area);
+
+        let text = self.text_area.lines().join(" ");
+        let (title, notes) = match search::Query::parse(&text, self.local_offset) {
+            Ok(query) if query.is_empty() => ("Search".to_string(), Vec::new()),
+            Ok(query) => {
+                let notes = model.search(&query);
+                (format!("Search ({} found)", notes.len()), notes)
+            }
+            Err(err) => (format!("Search ({err})"), Vec::new()),
+        };
+
+        self.text_area.set_block(
+            ratatui::widgets::Block::bordered()
+                .border_set(ratatui::symbols::border::THICK)
+                .title(title),
+        );
+        self.text_area.widget().render(layout[0], buf);
+
+        self.results = notes
+            .iter()
+            .map(|note| (note.inner.topic.clone(), note.id()))
+            .collect();
+
+        let items: Vec<_> = notes
+            .iter()
+            .map(|note| {
+                let created_at = note.inner.created_at.to_offset(self.local_offset);
+                ratatui::widgets::ListItem::new(vec![
+                    ratatui::text::Line::from(format!(
+                        "{} · {} · {} {:02}:{:02}",
+                        note.inner.topic,
+                        model.author_name(&note.pub_key),
+                        created_at.date(),
+                        created_at.hour(),
+                        created_at.minute()
+                    ))
+                    .bold(),
+                    ratatui::text::Line::from(format!("  {}", note.inner.msg)),
+                ])
+            })
+            .collect();
+
+        let list = ratatui::widgets::List::new(items)
+            .block(ratatui::widgets::Block::bordered().border_set(ratatui::symbols::border::THICK))
+            .style(ratatui::style::Style::default().fg(ratatui::style::Color::White))
+            .highlight_style(
+                ratatui::style::Style::default().add_modifier(ratatui::style::Modifier::REVERSED),
+            );
+
+        ratatui::widgets::StatefulWidget::render(list, layout[1], buf, &mut self.list_state);
+    }
+}
+
+use ratatui::layout::Constraint;
+use ratatui::layout::Direction;
+use ratatui::layout::Layout;
+use ratatui::style::Stylize;
+use ratatui::widgets::Widget;
+
+use crate::components;
+use crate::model;
+use crate::note;
+use crate::search;
@@ -0,0 +1,32 @@
+#[derive(Default)]
+pub struct StatusBar;
+
+impl StatusBar {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl components::Component for StatusBar {
+    fn update(&mut self, _event: crossterm::event::KeyEvent) -> components::Effect {
+        components::Effect::Nothing
+    }
+
+    fn render(
+        &mut self,
+        model: &model::Model,
+        area: ratatui::layout::Rect,
+        buf: &mut ratatui::buffer::Buffer,
+    ) {
+        let status = model.status.clone().unwrap_or_default();
+
+        ratatui::widgets::Paragraph::new(status)
+            .style(ratatui::style::Style::default().fg(ratatui::style::Color::Yellow))
+            .render(area, buf)
+    }
+}
+
+use ratatui::widgets::Widget;
+
+use crate::components;
+use crate::model;
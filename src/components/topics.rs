@@ -11,6 +11,18 @@ impl Topics {
         }
     }
 
+    /// Topics we didn't know about yet are added.
+    pub fn select(&mut self, topic: &str) {
+        let position = match self.known_topics.iter().position(|known| known == topic) {
+            Some(position) => position,
+            None => {
+                self.known_topics.push(topic.to_string());
+                self.known_topics.len() - 1
+            }
+        };
+        self.list_state.select(Some(position));
+    }
+
     pub fn selected_topic(&self) -> Option<&str> {
         self.list_state
             .selected()
@@ -18,6 +30,12 @@ impl Topics {
     }
 }
 
+impl Default for Topics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl components::Component for Topics {
     fn update(&mut self, event: crossterm::event::KeyEvent) -> components::Effect {
         match event.code {
@@ -36,13 +54,37 @@ impl components::Component for Topics {
 
     fn render(
         &mut self,
-        _model: &model::Model, // TODO: Reconcile with model
+        model: &model::Model,
         area: ratatui::layout::Rect,
         buf: &mut ratatui::buffer::Buffer,
     ) {
         let block = ratatui::widgets::Block::bordered().border_set(ratatui::symbols::border::THICK);
 
-        let items: Vec<_> = self.known_topics.clone();
+        // Topics with notes show up, even when nobody selected them here yet.
+        for topic in model.topics.keys() {
+            if !self.known_topics.contains(topic) {
+                self.known_topics.push(topic.clone());
+            }
+        }
+
+        let items: Vec<_> = self
+            .known_topics
+            .iter()
+            .map(|topic| {
+                let unread = model.unread(topic);
+                match unread {
+                    model::Unread { notes: 0, .. } => ratatui::text::Line::raw(topic.as_str()),
+                    model::Unread { notes, mentions: 0 } => {
+                        ratatui::text::Line::raw(format!("{topic} ({notes})")).bold()
+                    }
+                    model::Unread { notes, .. } => {
+                        ratatui::text::Line::raw(format!("{topic} ({notes}@)"))
+                            .bold()
+                            .fg(ratatui::style::Color::Yellow)
+                    }
+                }
+            })
+            .collect();
 
         let list = ratatui::widgets::List::new(items)
             .block(block)
@@ -1,26 +1,242 @@
 pub struct Controller {
     model: model::Model,
+    store: store::Store,
     swarm: libp2p::Swarm<Behavior>,
+    discovery_interval: tokio::time::Interval,
+    private_network: bool,
+    signing_key: note::SigningKey,
+    send_receipts: bool,
+    author_rate_limiter: rate_limit::RateLimiter<note::PubKey>,
+    peer_rate_limiter: rate_limit::RateLimiter<libp2p::PeerId>,
+    max_clock_skew: std::time::Duration,
+    clock: hlc::Clock,
+    notify_hook: Option<notify::Hook>,
+    max_message_size: usize,
+    compress_notes: bool,
+    download_dir: std::path::PathBuf,
+    downloads: BTreeMap<attachment::Hash, Download>,
+    chunk_requests:
+        HashMap<libp2p::request_response::RequestId, (attachment::Hash, attachment::Request)>,
+    /// The roots of the chunk lists each chunk we have is in. A chunk is only handed out for an attachment of a note.
+    chunk_roots: BTreeMap<attachment::Hash, BTreeSet<attachment::Hash>>,
+    /// Notes from gossip whose signatures are being checked, with the message they came in.
+    note_verifier: verify::Verifier<(libp2p::gossipsub::MessageId, libp2p::PeerId), note::Note>,
+    /// Parts of notes still being reassembled, with the message they came in. None is relayed before the note checks out.
+    part_messages:
+        BTreeMap<(note::PubKey, note::NoteId), Vec<(libp2p::gossipsub::MessageId, libp2p::PeerId)>>,
+    /// Certificates for keys we don't know yet, oldest first. Neither saved nor passed on again.
+    stray_certificates: VecDeque<note::Certificate>,
+    /// The messages to publish each note in the outbox with, signed once for all retries.
+    outbox_messages: BTreeMap<note::NoteId, Vec<(&'static str, Vec<u8>)>>,
+    /// Receipts being signed on the blocking pool, since an agent may take its time.
+    signing_receipts: FuturesUnordered<
+        tokio::task::JoinHandle<Result<note::Signed<note::Receipt>, note::SignError>>,
+    >,
+}
+
+/// What came of [`Controller::restore_identity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restore {
+    Restored,
+    /// We have another identity, which is only replaced when asked to.
+    Exists,
+    Failed,
+}
+
+/// An attachment we fetch from peers.
+struct Download {
+    attachment: note::Attachment,
+    /// Known once a peer sent a chunk list that matches the root.
+    chunks: Option<Vec<attachment::Hash>>,
+    missing: BTreeSet<attachment::Hash>,
+    /// Peers that have the chunk list, and so likely the chunks.
+    sources: Vec<libp2p::PeerId>,
+    pending_requests: usize,
 }
 
 #[derive(libp2p::swarm::NetworkBehaviour)]
 struct Behavior {
     gossipsub: libp2p::gossipsub::Behaviour,
-    mdns: libp2p::mdns::tokio::Behaviour,
+    mdns: libp2p::swarm::behaviour::toggle::Toggle<libp2p::mdns::tokio::Behaviour>,
+    kademlia: libp2p::kad::Behaviour<libp2p::kad::store::MemoryStore>,
+    identify: libp2p::identify::Behaviour,
+    relay_client: libp2p::relay::client::Behaviour,
+    dcutr: libp2p::dcutr::Behaviour,
+    ping: libp2p::ping::Behaviour,
+    chunks: libp2p::request_response::Behaviour<attachment::Codec>,
+    blocks: libp2p::allow_block_list::Behaviour<libp2p::allow_block_list::BlockedPeers>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Discover peers on the local network.
+    pub mdns: bool,
+    /// DHT entry points. Each address must end with `/p2p/<peer id>`.
+    pub bootstrap_peers: Vec<libp2p::Multiaddr>,
+    /// Relays we keep a reservation on, so peers behind NAT can reach us. Each address must end with `/p2p/<peer id>`.
+    pub relays: Vec<libp2p::Multiaddr>,
+    /// Only talk to peers holding the same key. Disables QUIC, since the key can only protect TCP.
+    pub swarm_key: Option<libp2p::pnet::PreSharedKey>,
+    /// Where we keep state across restarts. Nothing is kept without one, not even the attachments we hand out.
+    pub data_dir: Option<std::path::PathBuf>,
+    pub listen_addrs: Vec<libp2p::Multiaddr>,
+    /// How often we announce ourselves as provider of our topics and look for other providers.
+    pub discovery_interval: std::time::Duration,
+    /// Our identity as an author. Signs our notes and receipts.
+    pub signing_key: note::SigningKey,
+    /// Let authors know when we received their notes.
+    pub send_receipts: bool,
+    /// How many notes we take from one author, no matter who relays them.
+    pub author_rate_limit: rate_limit::RateLimit,
+    /// How many messages we take from one peer. Peers going over it are penalized until they are graylisted.
+    pub peer_rate_limit: rate_limit::RateLimit,
+    /// How far in the future a note may be dated before we refuse it, to allow for clocks that are a bit ahead.
+    pub max_clock_skew: std::time::Duration,
+    /// Run when a note mentions us.
+    pub notify_hook: Option<notify::Hook>,
+    /// Largest message we send or take. Larger notes are sent in parts, up to [`parts::MAX_NOTE_SIZE`].
+    pub max_message_size: usize,
+    /// Deflate the notes we send, when that makes them smaller. Notes are taken compressed or not either way.
+    pub compress_notes: bool,
+    /// Where saved attachments go.
+    pub download_dir: std::path::PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mdns: true,
+            bootstrap_peers: Vec::new(),
+            relays: Vec::new(),
+            swarm_key: None,
+            data_dir: None,
+            listen_addrs: vec![
+                "/ip4/0.0.0.0/udp/0/quic-v1"
+                    .parse()
+                    .expect("invalid quic address"),
+                "/ip4/0.0.0.0/tcp/0".parse().expect("invalid tcp address"),
+            ],
+            discovery_interval: std::time::Duration::from_secs(30),
+            signing_key: identity::Keypair::generate_ed25519().into(),
+            send_receipts: true,
+            author_rate_limit: rate_limit::RateLimit {
+                burst: 20,
+                interval: std::time::Duration::from_secs(1),
+            },
+            peer_rate_limit: rate_limit::RateLimit {
+                burst: 100,
+                interval: std::time::Duration::from_millis(100),
+            },
+            max_clock_skew: DEFAULT_MAX_CLOCK_SKEW,
+            notify_hook: None,
+            max_message_size: 64 * 1024,
+            compress_notes: true,
+            download_dir: dirs::download_dir().unwrap_or_else(std::env::temp_dir),
+        }
+    }
+}
+
+/// Also applies to notes we import, which don't go through a controller.
+pub const DEFAULT_MAX_CLOCK_SKEW: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+const GOSSIPSUB_TOPIC: &str = "n2p-test";
+const RECEIPTS_TOPIC: &str = "n2p-receipts";
+const PROFILES_TOPIC: &str = "n2p-profiles";
+const PARTS_TOPIC: &str = "n2p-parts";
+const CERTIFICATES_TOPIC: &str = "n2p-certificates";
+pub(crate) const KADEMLIA_PROTOCOL: libp2p::StreamProtocol =
+    libp2p::StreamProtocol::new("/n2p/kad/1.0.0");
+/// Room for the signature, key and topic gossipsub wraps messages in.
+const GOSSIPSUB_ENVELOPE_SIZE: usize = 1024;
+/// How long we wait for the remaining parts of a note.
+const PARTS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+/// How many certificates for keys we don't know we hold on to, in case the key shows up.
+const MAX_STRAY_CERTIFICATES: usize = 256;
+const IDENTIFY_PROTOCOL: &str = "/n2p/id/1.0.0";
+
+pub(crate) fn kademlia_behaviour(
+    key: &identity::Keypair,
+) -> libp2p::kad::Behaviour<libp2p::kad::store::MemoryStore> {
+    let peer_id = key.public().to_peer_id();
+    let mut config = libp2p::kad::Config::default();
+    config.set_protocol_names(vec![KADEMLIA_PROTOCOL]);
+
+    let mut kademlia = libp2p::kad::Behaviour::with_config(
+        peer_id,
+        libp2p::kad::store::MemoryStore::new(peer_id),
+        config,
+    );
+    // We can't rely on confirmed external addresses to switch to server mode, since most
+    // peers only ever know their local addresses.
+    kademlia.set_mode(Some(libp2p::kad::Mode::Server));
+    kademlia
+}
+
+pub(crate) fn identify_behaviour(key: &identity::Keypair) -> libp2p::identify::Behaviour {
+    libp2p::identify::Behaviour::new(
+        libp2p::identify::Config::new(IDENTIFY_PROTOCOL.to_string(), key.public())
+            .with_agent_version(format!("n2p/{}", env!("CARGO_PKG_VERSION"))),
+    )
+}
+
+pub(crate) fn transport(
+    key: &identity::Keypair,
+    swarm_key: Option<libp2p::pnet::PreSharedKey>,
+) -> Result<
+    libp2p::core::transport::Boxed<(libp2p::PeerId, libp2p::core::muxing::StreamMuxerBox)>,
+    libp2p::noise::Error,
+> {
+    let tcp = libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::default())
+        .and_then(move |socket, _| async move {
+            match swarm_key {
+                Some(swarm_key) => libp2p::pnet::PnetConfig::new(swarm_key)
+                    .handshake(socket)
+                    .await
+                    .map(futures::future::Either::Left),
+                None => Ok(futures::future::Either::Right(socket)),
+            }
+        })
+        .upgrade(libp2p::core::upgrade::Version::V1Lazy)
+        .authenticate(libp2p::noise::Config::new(key)?)
+        .multiplex(libp2p::yamux::Config::default())
+        .map(|(peer_id, muxer), _| (peer_id, libp2p::core::muxing::StreamMuxerBox::new(muxer)));
+
+    let quic = match swarm_key {
+        Some(_) => libp2p::core::transport::OptionalTransport::none(),
+        None => libp2p::core::transport::OptionalTransport::some(
+            libp2p::quic::tokio::Transport::new(libp2p::quic::Config::new(key)),
+        ),
+    }
+    .map(|(peer_id, muxer), _| (peer_id, libp2p::core::muxing::StreamMuxerBox::new(muxer)));
+
+    Ok(tcp
+        .or_transport(quic)
+        .map(|either, _| either.into_inner())
+        .boxed())
+}
+
+pub(crate) fn peer_id_of(addr: &libp2p::Multiaddr) -> anyhow::Result<libp2p::PeerId> {
+    match addr.iter().last() {
+        Some(libp2p::multiaddr::Protocol::P2p(peer_id)) => Ok(peer_id),
+        _ => anyhow::bail!("address {addr} does not end with a peer id"),
+    }
 }
 
 impl Controller {
-    pub fn new() -> anyhow::Result<Self> {
+    pub fn new(config: Config) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            config.max_message_size > 2 * parts::PART_OVERHEAD,
+            "max message size must be more than {} bytes",
+            2 * parts::PART_OVERHEAD
+        );
+
         let mut swarm = libp2p::SwarmBuilder::with_new_identity()
             .with_tokio()
-            .with_tcp(
-                libp2p::tcp::Config::default(),
-                libp2p::noise::Config::new,
-                libp2p::yamux::Config::default,
-            )
-            .context("failed to configure tcp for swarm")?
-            .with_quic()
-            .with_behaviour(|key| {
+            .with_other_transport(|key| transport(key, config.swarm_key).map_err(Into::into))
+            .context("failed to configure transport for swarm")?
+            .with_relay_client(libp2p::noise::Config::new, libp2p::yamux::Config::default)
+            .context("failed to configure relay client for swarm")?
+            .with_behaviour(|key, relay_client| {
                 // To content-address message, we can take the hash of message and use it as an ID.
                 let message_id_fn = |message: &libp2p::gossipsub::Message| {
                     let mut s = std::collections::hash_map::DefaultHasher::new();
@@ -33,20 +249,49 @@ impl Controller {
                     .heartbeat_interval(std::time::Duration::from_secs(10)) // This is set to aid debugging by not cluttering the log space
                     .validation_mode(libp2p::gossipsub::ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
                     .message_id_fn(message_id_fn) // content-address messages. No two messages of the same content will be propagated.
+                    .validate_messages() // Messages are only forwarded once we checked them, see `handle_message`.
+                    .max_transmit_size(config.max_message_size + GOSSIPSUB_ENVELOPE_SIZE) // Our limit is on what's in the envelope, see `handle_message`.
                     .build()
-                    .map_err(|msg| std::io::Error::new(std::io::ErrorKind::Other, msg))?; // Temporary hack because `build` does not return a proper `std::error::Error`.
+                    .map_err(std::io::Error::other)?; // Temporary hack because `build` does not return a proper `std::error::Error`.
 
                 // build a gossipsub network behaviour
-                let gossipsub = libp2p::gossipsub::Behaviour::new(
+                let mut gossipsub = libp2p::gossipsub::Behaviour::new(
                     libp2p::gossipsub::MessageAuthenticity::Signed(key.clone()),
                     gossipsub_config,
                 )?;
+                gossipsub
+                    .with_peer_score(
+                        peer_score_params(),
+                        libp2p::gossipsub::PeerScoreThresholds::default(),
+                    )
+                    .map_err(std::io::Error::other)?;
 
-                let mdns = libp2p::mdns::tokio::Behaviour::new(
-                    libp2p::mdns::Config::default(),
-                    key.public().to_peer_id(),
-                )?;
-                Ok(Behavior { gossipsub, mdns })
+                let mdns = if config.mdns {
+                    Some(libp2p::mdns::tokio::Behaviour::new(
+                        libp2p::mdns::Config::default(),
+                        key.public().to_peer_id(),
+                    )?)
+                } else {
+                    None
+                };
+
+                Ok(Behavior {
+                    gossipsub,
+                    mdns: mdns.into(),
+                    kademlia: kademlia_behaviour(key),
+                    identify: identify_behaviour(key),
+                    relay_client,
+                    dcutr: libp2p::dcutr::Behaviour::new(key.public().to_peer_id()),
+                    ping: libp2p::ping::Behaviour::default(),
+                    chunks: libp2p::request_response::Behaviour::new(
+                        [(
+                            attachment::PROTOCOL,
+                            libp2p::request_response::ProtocolSupport::Full,
+                        )],
+                        libp2p::request_response::Config::default(),
+                    ),
+                    blocks: Default::default(),
+                })
             })
             .context("failed to configure behavior for swarm")?
             .with_swarm_config(|c| {
@@ -54,134 +299,1363 @@ impl Controller {
             })
             .build();
 
-        // Create a Gossipsub topic
-        let topic = libp2p::gossipsub::IdentTopic::new("n2p-test");
-        // subscribes to our topic
-        swarm
-            .behaviour_mut()
-            .gossipsub
-            .subscribe(&topic)
-            .context("failed to subscribe to gossipsub topic")?;
+        // Receipts are gossiped separately, so we see them even after opting out of sending our own.
+        for topic in [
+            GOSSIPSUB_TOPIC,
+            RECEIPTS_TOPIC,
+            PROFILES_TOPIC,
+            PARTS_TOPIC,
+            CERTIFICATES_TOPIC,
+        ] {
+            swarm
+                .behaviour_mut()
+                .gossipsub
+                .subscribe(&libp2p::gossipsub::IdentTopic::new(topic))
+                .context("failed to subscribe to gossipsub topic")?;
+        }
+
+        for addr in config.listen_addrs {
+            swarm
+                .listen_on(addr.clone())
+                .with_context(|| format!("failed to listen on {addr}"))?;
+        }
+
+        for addr in &config.bootstrap_peers {
+            let peer_id = peer_id_of(addr)?;
+            swarm
+                .behaviour_mut()
+                .kademlia
+                .add_address(&peer_id, addr.clone());
+        }
+
+        if !config.bootstrap_peers.is_empty() {
+            swarm
+                .behaviour_mut()
+                .kademlia
+                .bootstrap()
+                .context("failed to bootstrap kademlia")?;
+        }
+
+        for relay in &config.relays {
+            peer_id_of(relay)?;
+            // Listening on a relay circuit makes the relay client keep a reservation on the relay.
+            swarm
+                .listen_on(relay.clone().with(libp2p::multiaddr::Protocol::P2pCircuit))
+                .with_context(|| format!("failed to listen through relay {relay}"))?;
+        }
+
+        let store = match &config.data_dir {
+            Some(dir) => store::Store::open(dir).context("failed to open store")?,
+            None => store::Store::in_memory(),
+        };
 
-        swarm
-            .listen_on("/ip4/0.0.0.0/udp/0/quic-v1".parse().unwrap())
-            .context("failed to listen with quic")?;
-        swarm
-            .listen_on("/ip4/0.0.0.0/tcp/0".parse().unwrap())
-            .context("failed to listen with tcp")?;
+        let migrated = store
+            .migrate_signatures(&config.signing_key)
+            .context("failed to re-sign stored notes")?;
+        let mut model = store.load_model().context("failed to load stored state")?;
+        model.me = Some(config.signing_key.public().into());
+        if migrated > 0 {
+            model.status = Some(format!("Re-signed {migrated} of our notes and profiles."));
+        }
+        let mut clock = hlc::Clock::new();
+        for note in model.topics.values().flat_map(|topic| topic.notes.values()) {
+            clock.observe(note.inner.hlc);
+        }
+        for peer_id in &model.blocks.peers {
+            swarm.behaviour_mut().blocks.block_peer(*peer_id);
+        }
+        let mut chunk_roots: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        let roots: BTreeSet<_> = model
+            .topics
+            .values()
+            .flat_map(|topic| topic.notes.values())
+            .flat_map(|note| &note.inner.attachments)
+            .map(|attachment| attachment.root)
+            .collect();
+        for root in roots {
+            let chunks = store
+                .load_chunk_list(&root)
+                .context("failed to load chunk list")?;
+            for hash in chunks.into_iter().flatten() {
+                chunk_roots.entry(hash).or_default().insert(root);
+            }
+        }
 
         Ok(Self {
-            model: model::Model::new(),
+            model,
+            store,
             swarm,
+            discovery_interval: tokio::time::interval(config.discovery_interval),
+            private_network: config.swarm_key.is_some(),
+            signing_key: config.signing_key,
+            send_receipts: config.send_receipts,
+            author_rate_limiter: rate_limit::RateLimiter::new(config.author_rate_limit),
+            peer_rate_limiter: rate_limit::RateLimiter::new(config.peer_rate_limit),
+            max_clock_skew: config.max_clock_skew,
+            clock,
+            notify_hook: config.notify_hook,
+            max_message_size: config.max_message_size,
+            compress_notes: config.compress_notes,
+            download_dir: config.download_dir,
+            downloads: BTreeMap::new(),
+            chunk_requests: HashMap::new(),
+            chunk_roots,
+            note_verifier: verify::Verifier::new(),
+            part_messages: BTreeMap::new(),
+            stray_certificates: VecDeque::new(),
+            outbox_messages: BTreeMap::new(),
+            signing_receipts: FuturesUnordered::new(),
+        })
+    }
+
+    /// A note that comes after everything we've seen in the topic.
+    pub fn compose_note(&mut self, topic: String, msg: String) -> note::Note {
+        let now = time::OffsetDateTime::now_utc();
+        let parents = self
+            .model
+            .topics
+            .get(&topic)
+            .map(|topic| topic.heads.iter().copied().collect())
+            .unwrap_or_default();
+
+        note::Note {
+            topic,
+            msg,
+            created_at: now,
+            hlc: self.clock.tick(now),
+            parents,
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Our notes show up right away, and are published as soon as there is someone to receive them.
+    pub fn send_note(&mut self, note: note::Signed<note::Note>) -> Result<(), parts::Error> {
+        let size = note.encode_to_vec()?.len();
+        if size > parts::MAX_NOTE_SIZE {
+            return Err(parts::Error::TooLarge {
+                size,
+                max: parts::MAX_NOTE_SIZE,
+            });
+        }
+
+        let messages = self.note_messages(&note)?;
+        self.outbox_messages.insert(note.id(), messages);
+
+        self.add_note(note.clone());
+        self.model.outbox.insert(note.id(), note);
+        self.save_outbox();
+        self.publish_outbox();
+        Ok(())
+    }
+
+    /// Keeps the file in the store for peers to fetch, and returns the manifest to put in a note.
+    pub fn attach(&mut self, name: String, data: &[u8]) -> Result<note::Attachment, store::Error> {
+        let chunks = data
+            .chunks(attachment::CHUNK_SIZE)
+            .map(|chunk| {
+                let hash = attachment::hash(chunk);
+                self.store.save_chunk(&hash, chunk)?;
+                Ok(hash)
+            })
+            .collect::<Result<Vec<_>, store::Error>>()?;
+        let root = attachment::root(&chunks);
+        self.store.save_chunk_list(&root, &chunks)?;
+        for hash in &chunks {
+            self.chunk_roots.entry(*hash).or_default().insert(root);
+        }
+
+        Ok(note::Attachment {
+            mime_type: attachment::mime_type(&name),
+            name,
+            size: data.len() as u64,
+            root,
         })
     }
 
-    pub fn send_note(&mut self, note: note::Signed<note::Note>) {
-        let topic = libp2p::gossipsub::IdentTopic::new("n2p-test");
-        let encoded_note = note.encode_to_vec().expect("failed to encode to vec");
-        self.swarm
+    /// Writes the attachment to the download directory, after fetching it from peers if we don't have it yet.
+    pub fn save_attachment(&mut self, attachment: note::Attachment) {
+        match self.store.open_attachment(&attachment) {
+            Ok(Some(reader)) => self.write_attachment(&attachment, reader),
+            Ok(None) => self.fetch_attachment(attachment),
+            Err(err) => {
+                self.model.status = Some(format!("Failed to load {}: {err}", attachment.name));
+                self.model
+                    .transfers
+                    .insert(attachment.root, model::Transfer::Failed);
+            }
+        }
+    }
+
+    fn fetch_attachment(&mut self, attachment: note::Attachment) {
+        if self.downloads.contains_key(&attachment.root) {
+            return;
+        }
+
+        // We can't tell which peer has it, so we ask everyone.
+        let peers: Vec<_> = self.swarm.connected_peers().copied().collect();
+        if peers.is_empty() {
+            self.model.status = Some(format!("No peers to fetch {} from", attachment.name));
+            self.model
+                .transfers
+                .insert(attachment.root, model::Transfer::Failed);
+            return;
+        }
+
+        let root = attachment.root;
+        self.downloads.insert(
+            root,
+            Download {
+                attachment,
+                chunks: None,
+                missing: BTreeSet::new(),
+                sources: Vec::new(),
+                pending_requests: 0,
+            },
+        );
+        self.model.transfers.insert(
+            root,
+            model::Transfer::Fetching {
+                received: 0,
+                total: 0,
+            },
+        );
+        for peer_id in peers {
+            self.request_chunks(peer_id, root, attachment::Request::ChunkList(root));
+        }
+    }
+
+    fn request_chunks(
+        &mut self,
+        peer_id: libp2p::PeerId,
+        root: attachment::Hash,
+        request: attachment::Request,
+    ) {
+        let Some(download) = self.downloads.get_mut(&root) else {
+            return;
+        };
+
+        download.pending_requests += 1;
+        let request_id = self
+            .swarm
             .behaviour_mut()
-            .gossipsub
-            .publish(topic.clone(), encoded_note)
-            .expect("publish note failed");
+            .chunks
+            .send_request(&peer_id, request.clone());
+        self.chunk_requests.insert(request_id, (root, request));
+    }
+
+    fn handle_chunk_request(
+        &mut self,
+        request: attachment::Request,
+        channel: libp2p::request_response::ResponseChannel<attachment::Response>,
+    ) {
+        // Only what notes we have point to is handed out, not everything that ever was in the store.
+        let response = match request {
+            attachment::Request::ChunkList(root) if self.model.has_attachment(&root) => self
+                .store
+                .load_chunk_list(&root)
+                .ok()
+                .flatten()
+                .map(attachment::Response::ChunkList),
+            attachment::Request::Chunk(hash)
+                if self.chunk_roots.get(&hash).is_some_and(|roots| {
+                    roots.iter().any(|root| self.model.has_attachment(root))
+                }) =>
+            {
+                self.store
+                    .load_chunk(&hash)
+                    .ok()
+                    .flatten()
+                    .map(attachment::Response::Chunk)
+            }
+            _ => None,
+        };
+
+        // Fails only if the peer is gone already.
+        let _ = self
+            .swarm
+            .behaviour_mut()
+            .chunks
+            .send_response(channel, response.unwrap_or(attachment::Response::NotFound));
+    }
+
+    /// Anything that doesn't match the hashes we asked for counts as a failure of the peer.
+    fn handle_chunk_response(
+        &mut self,
+        peer_id: libp2p::PeerId,
+        request_id: libp2p::request_response::RequestId,
+        response: Option<attachment::Response>,
+    ) {
+        let Some((root, request)) = self.chunk_requests.remove(&request_id) else {
+            return;
+        };
+        let Some(download) = self.downloads.get_mut(&root) else {
+            return;
+        };
+        download.pending_requests -= 1;
+
+        match (request, response) {
+            (attachment::Request::ChunkList(_), Some(attachment::Response::ChunkList(chunks)))
+                if attachment::root(&chunks) == root
+                    && chunks.len() as u64
+                        == download
+                            .attachment
+                            .size
+                            .div_ceil(attachment::CHUNK_SIZE as u64) =>
+            {
+                download.sources.push(peer_id);
+                if download.chunks.is_none() {
+                    download.missing = chunks
+                        .iter()
+                        .filter(|hash| !matches!(self.store.load_chunk(hash), Ok(Some(_))))
+                        .copied()
+                        .collect();
+                    if let Err(err) = self.store.save_chunk_list(&root, &chunks) {
+                        self.model.status = Some(format!("Failed to save chunk list: {err}"));
+                    }
+                    for hash in &chunks {
+                        self.chunk_roots.entry(*hash).or_default().insert(root);
+                    }
+                    download.chunks = Some(chunks);
+
+                    let missing: Vec<_> = download.missing.iter().copied().collect();
+                    for hash in missing {
+                        self.request_chunks(peer_id, root, attachment::Request::Chunk(hash));
+                    }
+                }
+            }
+
+            (attachment::Request::Chunk(hash), Some(attachment::Response::Chunk(chunk)))
+                if chunk.len() <= attachment::CHUNK_SIZE && attachment::hash(&chunk) == hash =>
+            {
+                if download.missing.remove(&hash) {
+                    if let Err(err) = self.store.save_chunk(&hash, &chunk) {
+                        self.model.status = Some(format!("Failed to save chunk: {err}"));
+                    }
+                }
+            }
+
+            // Another peer that had the chunk list may have the chunk.
+            (attachment::Request::Chunk(hash), _) => {
+                download.sources.retain(|source| *source != peer_id);
+                if let Some(source) = download.sources.first().copied() {
+                    self.request_chunks(source, root, attachment::Request::Chunk(hash));
+                }
+            }
+
+            (attachment::Request::ChunkList(_), _) => (),
+        }
+
+        self.update_download(root);
+    }
+
+    fn update_download(&mut self, root: attachment::Hash) {
+        let Some(download) = self.downloads.get(&root) else {
+            return;
+        };
+
+        match &download.chunks {
+            Some(_) if download.missing.is_empty() => {
+                let download = self.downloads.remove(&root).expect("download is there");
+                self.save_attachment(download.attachment);
+            }
+            _ if download.pending_requests == 0 => {
+                let download = self.downloads.remove(&root).expect("download is there");
+                self.model.status = Some(format!("Failed to fetch {}", download.attachment.name));
+                self.model.transfers.insert(root, model::Transfer::Failed);
+            }
+            chunks => {
+                let total = chunks.as_ref().map_or(0, Vec::len);
+                self.model.transfers.insert(
+                    root,
+                    model::Transfer::Fetching {
+                        received: total - download.missing.len(),
+                        total,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Never overwrites a file, nor writes outside the download directory whatever the name says.
+    fn write_attachment(&mut self, attachment: &note::Attachment, mut data: impl std::io::Read) {
+        let name = std::path::Path::new(&attachment.name)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "attachment".to_string());
+        let prefix: String = attachment.root[..4]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        let result = [name.clone(), format!("{prefix}-{name}")]
+            .into_iter()
+            .map(|name| self.download_dir.join(name))
+            .find(|path| !path.exists())
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::AlreadyExists))
+            .and_then(|path| {
+                std::fs::create_dir_all(&self.download_dir)?;
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .and_then(|mut file| std::io::copy(&mut data, &mut file))?;
+                Ok(path)
+            });
+
+        match result {
+            Ok(path) => {
+                self.model.status = Some(format!("Saved {}", path.display()));
+                self.model
+                    .transfers
+                    .insert(attachment.root, model::Transfer::Saved(path));
+            }
+            Err(err) => {
+                self.model.status = Some(format!("Failed to save {}: {err}", attachment.name));
+                self.model
+                    .transfers
+                    .insert(attachment.root, model::Transfer::Failed);
+            }
+        }
+    }
+
+    /// Adds the note to the model and the history, unless we have it already.
+    fn add_note(&mut self, note: note::Signed<note::Note>) {
+        if self.model.contains(&note.id()) {
+            return;
+        }
+
+        if let Err(err) = self.store.append_notes([&note]) {
+            self.model.status = Some(format!("Failed to save note: {err}"));
+        }
+        let pub_key = note.pub_key.clone();
+        let new_author = !self.model.index.has_author(&pub_key);
+        self.model.add_note(note);
+        if new_author {
+            self.adopt_certificates(&pub_key);
+        }
+    }
+
+    /// Everything in the topic so far counts as read, now and after a restart.
+    pub fn mark_read(&mut self, topic: &str) {
+        if !self.model.mark_read(topic) {
+            return;
+        }
+        if let Err(err) = self.store.save_read(&self.model.read) {
+            self.model.status = Some(format!("Failed to save read notes: {err}"));
+        }
+    }
+
+    /// Adds the author to our contacts if they aren't there yet.
+    pub fn update_contact(
+        &mut self,
+        pub_key: note::PubKey,
+        update: impl FnOnce(&mut model::Contact),
+    ) {
+        update(self.model.contacts.entry(pub_key.clone()).or_default());
+        self.adopt_certificates(&pub_key);
+        if let Err(err) = self.store.save_contacts(&self.model.contacts) {
+            self.model.status = Some(format!("Failed to save contacts: {err}"));
+        }
+    }
+
+    /// Replaces our profile and tells everyone about it.
+    pub fn set_profile(&mut self, profile: note::Profile) {
+        let profile = match profile.sign(&self.signing_key) {
+            Ok(profile) => profile,
+            Err(err) => {
+                self.model.status = Some(format!("Failed to sign profile: {err}"));
+                return;
+            }
+        };
+        self.model.add_profile(profile);
+        self.save_profiles();
+        self.publish_profile();
+    }
+
+    /// Keeps a rotation or revocation of one of our keys and tells everyone about it.
+    pub fn certify(&mut self, certificate: note::Certificate) {
+        self.model.add_certificate(certificate);
+        self.save_certificates();
+        self.publish_certificates();
+    }
+
+    /// Drops everything we have from the author and ignores whatever they send from now on.
+    pub fn block_author(&mut self, pub_key: note::PubKey) {
+        self.model.remove_notes_by(&pub_key);
+        self.model.profiles.remove(&pub_key);
+        self.model.blocks.authors.insert(pub_key);
+        self.save_blocks();
+        self.save_profiles();
+    }
+
+    /// Notes they send from now on are accepted again. Dropped ones are gone.
+    pub fn unblock_author(&mut self, pub_key: &note::PubKey) {
+        self.model.blocks.authors.remove(pub_key);
+        self.save_blocks();
+    }
+
+    /// Closes connections to the peer and refuses new ones.
+    pub fn block_peer(&mut self, peer_id: libp2p::PeerId) {
+        self.swarm.behaviour_mut().blocks.block_peer(peer_id);
+        self.model.blocks.peers.insert(peer_id);
+        self.save_blocks();
+    }
+
+    pub fn unblock_peer(&mut self, peer_id: &libp2p::PeerId) {
+        self.swarm.behaviour_mut().blocks.unblock_peer(*peer_id);
+        self.model.blocks.peers.remove(peer_id);
+        self.save_blocks();
+    }
+
+    pub fn mute(&mut self, topic: String, pub_key: note::PubKey) {
         self.model
-            .topics
-            .entry(note.inner.topic.clone())
+            .blocks
+            .mutes
+            .entry(topic)
             .or_default()
-            .add_note(note);
+            .insert(pub_key);
+        self.save_blocks();
+    }
+
+    pub fn unmute(&mut self, topic: &str, pub_key: &note::PubKey) {
+        if let Some(muted) = self.model.blocks.mutes.get_mut(topic) {
+            muted.remove(pub_key);
+            if muted.is_empty() {
+                self.model.blocks.mutes.remove(topic);
+            }
+        }
+        self.save_blocks();
+    }
+
+    fn save_blocks(&mut self) {
+        if let Err(err) = self.store.save_blocks(&self.model.blocks) {
+            self.model.status = Some(format!("Failed to save blocks: {err}"));
+        }
+    }
+
+    fn publish_profile(&mut self) {
+        let Some(profile) = self.model.profiles.get(&self.signing_key.public().into()) else {
+            return;
+        };
+        let encoded_profile = profile.encode_to_vec().expect("failed to encode to vec");
+
+        // Peers that miss it now get it when they subscribe.
+        let _ = self.swarm.behaviour_mut().gossipsub.publish(
+            libp2p::gossipsub::IdentTopic::new(PROFILES_TOPIC),
+            encoded_profile,
+        );
+    }
+
+    fn save_profiles(&mut self) {
+        if let Err(err) = self.store.save_profiles(&self.model.profiles) {
+            self.model.status = Some(format!("Failed to save profiles: {err}"));
+        }
+    }
+
+    /// Certificates are passed on, whoever they're from, so they reach everyone who knows the key. Only those we
+    /// keep, for keys we know.
+    fn publish_certificates(&mut self) {
+        for certificate in self.model.keys.certificates() {
+            let encoded_certificate = certificate
+                .encode_to_vec()
+                .expect("failed to encode to vec");
+            let _ = self.swarm.behaviour_mut().gossipsub.publish(
+                libp2p::gossipsub::IdentTopic::new(CERTIFICATES_TOPIC),
+                encoded_certificate,
+            );
+        }
+    }
+
+    /// Contacts may have followed a rotation, so they're saved along with the certificates.
+    fn save_certificates(&mut self) {
+        if let Err(err) = self
+            .store
+            .save_certificates(&self.model.keys.certificates())
+            .and_then(|()| self.store.save_contacts(&self.model.contacts))
+        {
+            self.model.status = Some(format!("Failed to save certificates: {err}"));
+        }
+    }
+
+    fn publish_outbox(&mut self) {
+        let mut published = Vec::new();
+
+        let outbox: Vec<_> = self.model.outbox.values().cloned().collect();
+        for note in outbox {
+            let id = note.id();
+            // Notes from the outbox we left with are split once they are first published.
+            if !self.outbox_messages.contains_key(&id) {
+                match self.note_messages(&note) {
+                    Ok(messages) => {
+                        self.outbox_messages.insert(id, messages);
+                    }
+                    // The agent may sign by the next retry, the note waits in the outbox until then.
+                    Err(parts::Error::Signing(err)) => {
+                        self.model.status = Some(format!("Failed to sign note parts: {err}"));
+                        continue;
+                    }
+                    Err(err) => {
+                        self.model.status = Some(format!("Failed to split note: {err}"));
+                        continue;
+                    }
+                }
+            }
+            match self.publish_messages(self.outbox_messages[&id].clone()) {
+                Ok(()) => published.push(id),
+                // Nobody to send to yet, we try again when a peer subscribes.
+                Err(libp2p::gossipsub::PublishError::InsufficientPeers) => break,
+                Err(err) => {
+                    self.model.status = Some(format!("Failed to publish note: {err}"));
+                    break;
+                }
+            }
+        }
+
+        if published.is_empty() {
+            return;
+        }
+
+        for id in published {
+            self.model.outbox.remove(&id);
+            self.outbox_messages.remove(&id);
+            self.model.delivered.insert(id);
+        }
+        self.save_outbox();
+    }
+
+    /// Notes too large for one message are published in parts. Each part is published again on a retry, but peers
+    /// that got it already don't take it twice.
+    fn note_messages(
+        &self,
+        note: &note::Signed<note::Note>,
+    ) -> Result<Vec<(&'static str, Vec<u8>)>, parts::Error> {
+        let encoded_note = if self.compress_notes {
+            note.encode_compressed()
+        } else {
+            note.encode_to_vec()
+        }?;
+        if encoded_note.len() <= self.max_message_size {
+            return Ok(vec![(GOSSIPSUB_TOPIC, encoded_note)]);
+        }
+
+        parts::split(
+            note.id(),
+            &encoded_note,
+            self.max_message_size,
+            &self.signing_key,
+        )?
+        .iter()
+        .map(|part| Ok((PARTS_TOPIC, part.encode_to_vec()?)))
+        .collect()
+    }
+
+    fn publish_messages(
+        &mut self,
+        messages: Vec<(&'static str, Vec<u8>)>,
+    ) -> Result<(), libp2p::gossipsub::PublishError> {
+        for (topic, message) in messages {
+            match self
+                .swarm
+                .behaviour_mut()
+                .gossipsub
+                .publish(libp2p::gossipsub::IdentTopic::new(topic), message)
+            {
+                Ok(_) | Err(libp2p::gossipsub::PublishError::Duplicate) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn save_outbox(&mut self) {
+        if let Err(err) = self.store.save_outbox(self.model.outbox.values()) {
+            self.model.status = Some(format!("Failed to save outbox: {err}"));
+        }
     }
 
     pub async fn poll(&mut self) {
-        let event = self.swarm.select_next_some().await;
+        tokio::select! {
+            event = self.swarm.select_next_some() => self.handle_swarm_event(event),
+            verified = self.note_verifier.next() => self.handle_verified_notes(verified),
+            Some(receipt) = self.signing_receipts.next(), if !self.signing_receipts.is_empty() => {
+                self.publish_receipt(receipt.expect("signing doesn't panic"));
+            }
+            _ = self.discovery_interval.tick() => {
+                self.discover_topic_peers();
+                self.author_rate_limiter.prune(std::time::Instant::now());
+                self.peer_rate_limiter.prune(std::time::Instant::now());
+                if let Some(before) = std::time::Instant::now().checked_sub(PARTS_TIMEOUT) {
+                    self.prune_parts(before);
+                }
+            }
+        }
+    }
 
+    fn handle_swarm_event<E>(&mut self, event: libp2p::swarm::SwarmEvent<BehaviorEvent, E>) {
         match event {
+            // With a wrong swarm key the handshake itself goes through, but nothing after it makes sense.
+            libp2p::swarm::SwarmEvent::OutgoingConnectionError {
+                peer_id: Some(peer_id),
+                error: libp2p::swarm::DialError::Transport(_),
+                ..
+            } if self.private_network => {
+                self.model.status = Some(format!(
+                    "Failed to connect to {peer_id}. Does it use the same swarm key as you?"
+                ));
+            }
+
+            libp2p::swarm::SwarmEvent::IncomingConnectionError {
+                send_back_addr,
+                error: libp2p::swarm::ListenError::Transport(_),
+                ..
+            } if self.private_network => {
+                self.model.status = Some(format!(
+                    "Rejected connection from {send_back_addr}. Does it use the same swarm key as you?"
+                ));
+            }
+
             libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Mdns(
                 libp2p::mdns::Event::Discovered(peers),
             )) => {
-                for (peer_id, _multiaddr) in peers {
+                for (peer_id, multiaddr) in peers {
                     //println!("mDNS discovered a new peer: {peer_id}");
                     self.swarm
                         .behaviour_mut()
                         .gossipsub
                         .add_explicit_peer(&peer_id);
+                    self.model
+                        .peers
+                        .entry(peer_id)
+                        .or_default()
+                        .addresses
+                        .insert(multiaddr);
                 }
             }
 
             libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Mdns(
                 libp2p::mdns::Event::Expired(peers),
             )) => {
-                for (peer_id, _multiaddr) in peers {
+                for (peer_id, multiaddr) in peers {
                     //println!("mDNS discovered peer has expired: {peer_id}");
                     self.swarm
                         .behaviour_mut()
                         .gossipsub
                         .remove_explicit_peer(&peer_id);
+                    if let Some(peer) = self.model.peers.get_mut(&peer_id) {
+                        peer.addresses.remove(&multiaddr);
+                    }
+                    self.forget_peer_if_unknown(peer_id);
                 }
             }
 
             libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Gossipsub(
-                libp2p::gossipsub::Event::Message { message, .. },
+                libp2p::gossipsub::Event::Message {
+                    propagation_source,
+                    message_id,
+                    message,
+                },
             )) => {
-                //println!("Got message {message_id} from {propagation_source}");
-                let note = note::Signed::<note::Note>::decode(message.data.as_slice())
-                    .expect("decode failed");
-                self.model
-                    .topics
-                    .entry(note.inner.topic.clone())
-                    .or_default()
-                    .add_note(note);
+                if let Some(acceptance) =
+                    self.handle_message(message_id.clone(), propagation_source, message)
+                {
+                    self.report_message(&message_id, &propagation_source, acceptance);
+                }
             }
 
-            _other => {}
-        }
-    }
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Identify(
+                libp2p::identify::Event::Received { peer_id, info },
+            )) => {
+                if let Some(peer) = self.model.peers.get_mut(&peer_id) {
+                    peer.agent_version = Some(info.agent_version);
+                }
 
-    pub fn model(&self) -> &model::Model {
-        &self.model
-    }
-}
+                // Peers that dialed us are only routable once we know where they listen.
+                if info.protocols.contains(&KADEMLIA_PROTOCOL) {
+                    for addr in info.listen_addrs {
+                        self.swarm
+                            .behaviour_mut()
+                            .kademlia
+                            .add_address(&peer_id, addr);
+                    }
+                }
+            }
 
-use crate::note::Decode as _;
-use crate::note::Encode as _;
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Ping(libp2p::ping::Event {
+                peer,
+                result: Ok(rtt),
+                ..
+            })) => {
+                if let Some(peer) = self.model.peers.get_mut(&peer) {
+                    peer.rtt = Some(rtt);
+                }
+            }
 
-use anyhow::Context;
-use libp2p::futures::StreamExt as _;
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Chunks(
+                libp2p::request_response::Event::Message { peer, message },
+            )) => match message {
+                libp2p::request_response::Message::Request {
+                    request, channel, ..
+                } => self.handle_chunk_request(request, channel),
+                libp2p::request_response::Message::Response {
+                    request_id,
+                    response,
+                } => self.handle_chunk_response(peer, request_id, Some(response)),
+            },
 
-use std::hash::Hash as _;
-use std::hash::Hasher as _;
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Chunks(
+                libp2p::request_response::Event::OutboundFailure {
+                    peer, request_id, ..
+                },
+            )) => self.handle_chunk_response(peer, request_id, None),
 
-use crate::model;
-use crate::note;
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Gossipsub(
+                libp2p::gossipsub::Event::Subscribed { peer_id, topic },
+            )) => {
+                if let Some(peer) = self.model.peers.get_mut(&peer_id) {
+                    peer.topics.insert(topic.to_string());
+                }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+                if topic == libp2p::gossipsub::IdentTopic::new(GOSSIPSUB_TOPIC).hash()
+                    || topic == libp2p::gossipsub::IdentTopic::new(PARTS_TOPIC).hash()
+                {
+                    self.publish_outbox();
+                } else if topic == libp2p::gossipsub::IdentTopic::new(PROFILES_TOPIC).hash() {
+                    self.publish_profile();
+                } else if topic == libp2p::gossipsub::IdentTopic::new(CERTIFICATES_TOPIC).hash() {
+                    self.publish_certificates();
+                }
+            }
 
-    use fake::Fake as _;
-    use libp2p::identity;
-    use note::Sign as _;
-    use rand::RngCore as _;
-    use rand::SeedableRng as _;
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Gossipsub(
+                libp2p::gossipsub::Event::Unsubscribed { peer_id, topic },
+            )) => {
+                if let Some(peer) = self.model.peers.get_mut(&peer_id) {
+                    peer.topics.remove(&topic.to_string());
+                }
+            }
 
-    #[tokio::test]
-    async fn controllers_should_be_able_to_communicate() {
-        let mut c1 = Controller::new().unwrap();
+            libp2p::swarm::SwarmEvent::ConnectionEstablished {
+                peer_id, endpoint, ..
+            } => {
+                let addr = endpoint.get_remote_address();
+                let peer = self.model.peers.entry(peer_id).or_default();
+                peer.addresses.insert(addr.clone());
+                peer.transport = Some(model::Transport::of(addr));
+                peer.connected_since
+                    .get_or_insert_with(time::OffsetDateTime::now_utc);
+            }
 
-        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
-        let mut secret_key_bytes = [0; 32];
-        rng.fill_bytes(&mut secret_key_bytes);
-        let keypair = identity::Keypair::ed25519_from_bytes(secret_key_bytes)
-            .expect("Failed to generate keypair");
+            libp2p::swarm::SwarmEvent::ConnectionClosed {
+                peer_id,
+                endpoint,
+                num_established,
+                ..
+            } => {
+                if let Some(peer) = self.model.peers.get_mut(&peer_id) {
+                    peer.addresses.remove(endpoint.get_remote_address());
+                    if num_established == 0 {
+                        *peer = model::Peer {
+                            addresses: std::mem::take(&mut peer.addresses),
+                            ..Default::default()
+                        };
+                    }
+                }
+                self.forget_peer_if_unknown(peer_id);
+            }
 
-        let note: note::Note = fake::Faker.fake_with_rng(&mut rng);
-        let signed = note.sign(&keypair).expect("Failed to sign note");
-        let s1 = signed.clone();
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Kademlia(
+                libp2p::kad::Event::OutboundQueryProgressed {
+                    result:
+                        libp2p::kad::QueryResult::GetProviders(Ok(
+                            libp2p::kad::GetProvidersOk::FoundProviders { providers, .. },
+                        )),
+                    ..
+                },
+            )) => {
+                for peer_id in providers {
+                    if peer_id != *self.swarm.local_peer_id() && !self.swarm.is_connected(&peer_id)
+                    {
+                        // The addresses are only known while the query is running, so we dial right away.
+                        let _ = self.swarm.dial(peer_id);
+                    }
+                }
+            }
 
-        for _ in 0..10 {
-            c1.poll().await;
+            _other => {}
         }
+    }
 
-        let mut c2 = Controller::new().unwrap();
+    /// Rejecting a message lowers the score of the peer that sent it, ignoring it only stops it from spreading.
+    /// Nothing while its signature is checked, see [`Controller::handle_verified_notes`], or the rest of its note is
+    /// awaited, see [`Controller::handle_part`].
+    fn handle_message(
+        &mut self,
+        message_id: libp2p::gossipsub::MessageId,
+        propagation_source: libp2p::PeerId,
+        message: libp2p::gossipsub::Message,
+    ) -> Option<libp2p::gossipsub::MessageAcceptance> {
+        if message.data.len() > self.max_message_size
+            || !self
+                .peer_rate_limiter
+                .check(propagation_source, std::time::Instant::now())
+        {
+            return Some(libp2p::gossipsub::MessageAcceptance::Reject);
+        }
+
+        if message.topic == libp2p::gossipsub::IdentTopic::new(RECEIPTS_TOPIC).hash() {
+            Some(self.handle_receipt(&message.data))
+        } else if message.topic == libp2p::gossipsub::IdentTopic::new(PROFILES_TOPIC).hash() {
+            Some(self.handle_profile(&message.data))
+        } else if message.topic == libp2p::gossipsub::IdentTopic::new(PARTS_TOPIC).hash() {
+            self.handle_part(message_id, propagation_source, &message.data)
+        } else if message.topic == libp2p::gossipsub::IdentTopic::new(CERTIFICATES_TOPIC).hash() {
+            Some(self.handle_certificate(&message.data))
+        } else {
+            self.handle_note(message_id, propagation_source, &message.data)
+        }
+    }
+
+    fn report_message(
+        &mut self,
+        message_id: &libp2p::gossipsub::MessageId,
+        propagation_source: &libp2p::PeerId,
+        acceptance: libp2p::gossipsub::MessageAcceptance,
+    ) {
+        // Fails only if the message was already dropped from the cache, then there is nothing left to do.
+        let _ = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .report_message_validation_result(message_id, propagation_source, acceptance);
+    }
+
+    /// Notes come in by the thousand when peers catch us up, so their signatures are checked in batches off the task.
+    fn handle_note(
+        &mut self,
+        message_id: libp2p::gossipsub::MessageId,
+        propagation_source: libp2p::PeerId,
+        data: &[u8],
+    ) -> Option<libp2p::gossipsub::MessageAcceptance> {
+        let Ok(note) = note::Signed::<note::Note>::decode_compressed(data) else {
+            return Some(libp2p::gossipsub::MessageAcceptance::Reject);
+        };
+
+        self.note_verifier
+            .push((message_id, propagation_source), note);
+        None
+    }
+
+    fn handle_verified_notes(
+        &mut self,
+        verified: Vec<verify::Verified<(libp2p::gossipsub::MessageId, libp2p::PeerId), note::Note>>,
+    ) {
+        for verify::Verified {
+            context: (message_id, propagation_source),
+            signed: note,
+            valid,
+        } in verified
+        {
+            let acceptance = if valid {
+                self.accept_note(note)
+            } else {
+                libp2p::gossipsub::MessageAcceptance::Reject
+            };
+            self.report_message(&message_id, &propagation_source, acceptance);
+        }
+    }
+
+    /// Parts are taken as long as they fit together, but only relayed along with the rest once the note they make up
+    /// is checked like any other. Until then, nothing is reported.
+    fn handle_part(
+        &mut self,
+        message_id: libp2p::gossipsub::MessageId,
+        propagation_source: libp2p::PeerId,
+        data: &[u8],
+    ) -> Option<libp2p::gossipsub::MessageAcceptance> {
+        let Ok(part) = note::Signed::<note::Part>::decode(data) else {
+            return Some(libp2p::gossipsub::MessageAcceptance::Reject);
+        };
+        if !part.verify() {
+            return Some(libp2p::gossipsub::MessageAcceptance::Reject);
+        }
+
+        let key = (part.pub_key.clone(), part.inner.note_id);
+        let acceptance =
+            match self
+                .model
+                .parts
+                .add(part, propagation_source, std::time::Instant::now())
+            {
+                Ok(None) => {
+                    self.part_messages
+                        .entry(key)
+                        .or_default()
+                        .push((message_id, propagation_source));
+                    return None;
+                }
+                Ok(Some(note)) if note.verify() => self.accept_note(note),
+                // We may just be busy, then the note is up to whoever has room.
+                Err(parts::Error::Busy) => libp2p::gossipsub::MessageAcceptance::Ignore,
+                Ok(Some(_)) | Err(_) => libp2p::gossipsub::MessageAcceptance::Reject,
+            };
+
+        // The other parts came from peers that may have just passed them on, so they aren't penalized for a bad note.
+        let accepted = matches!(acceptance, libp2p::gossipsub::MessageAcceptance::Accept);
+        for (message_id, propagation_source) in self.part_messages.remove(&key).unwrap_or_default()
+        {
+            let others = if accepted {
+                libp2p::gossipsub::MessageAcceptance::Accept
+            } else {
+                libp2p::gossipsub::MessageAcceptance::Ignore
+            };
+            self.report_message(&message_id, &propagation_source, others);
+        }
+
+        Some(acceptance)
+    }
+
+    fn prune_parts(&mut self, before: std::time::Instant) {
+        for key in self.model.parts.prune(before) {
+            for (message_id, propagation_source) in
+                self.part_messages.remove(&key).unwrap_or_default()
+            {
+                self.report_message(
+                    &message_id,
+                    &propagation_source,
+                    libp2p::gossipsub::MessageAcceptance::Ignore,
+                );
+            }
+        }
+    }
+
+    fn accept_note(
+        &mut self,
+        note: note::Signed<note::Note>,
+    ) -> libp2p::gossipsub::MessageAcceptance {
+        // Whoever relays a blocked or flooding author may not be at fault, so they aren't penalized.
+        // Neither are they for notes from the future, their clock might just be ahead of ours.
+        let latest = time::OffsetDateTime::now_utc() + self.max_clock_skew;
+        if self.model.is_blocked(&note.pub_key)
+            || note.inner.is_after(latest)
+            || !self
+                .author_rate_limiter
+                .check(note.pub_key.clone(), std::time::Instant::now())
+        {
+            return libp2p::gossipsub::MessageAcceptance::Ignore;
+        }
+
+        let id = note.id();
+        let is_own_note = self.model.is_mine(&note.pub_key);
+        self.clock.observe(note.inner.hlc);
+        if !is_own_note && !self.model.contains(&id) && self.model.mentions_me(&note.inner) {
+            self.notify_mention(&note);
+        }
+        self.add_note(note);
+
+        if self.send_receipts && !is_own_note {
+            self.send_receipt(id);
+        }
+
+        libp2p::gossipsub::MessageAcceptance::Accept
+    }
+
+    fn notify_mention(&mut self, note: &note::Signed<note::Note>) {
+        if self.model.is_muted(&note.inner.topic, &note.pub_key) {
+            return;
+        }
+
+        let author = self.model.author_name(&note.pub_key);
+        self.model.status = Some(format!("{author} mentioned you in {}", note.inner.topic));
+        if let Some(hook) = &self.notify_hook {
+            if let Err(err) = hook.run(&note.inner.topic, &author, &note.inner.msg) {
+                self.model.status = Some(format!("Failed to run notification command: {err}"));
+            }
+        }
+    }
+
+    fn send_receipt(&mut self, note_id: note::NoteId) {
+        let signing_key = self.signing_key.clone();
+        self.signing_receipts
+            .push(tokio::task::spawn_blocking(move || {
+                note::Receipt { note_id }.sign(&signing_key)
+            }));
+    }
+
+    fn publish_receipt(&mut self, receipt: Result<note::Signed<note::Receipt>, note::SignError>) {
+        let receipt = match receipt {
+            Ok(receipt) => receipt,
+            Err(err) => {
+                self.model.status = Some(format!("Failed to sign receipt: {err}"));
+                return;
+            }
+        };
+        let encoded_receipt = receipt.encode_to_vec().expect("failed to encode to vec");
+
+        // Receipts are best effort. If nobody is around to hear it, the author won't be either.
+        let _ = self.swarm.behaviour_mut().gossipsub.publish(
+            libp2p::gossipsub::IdentTopic::new(RECEIPTS_TOPIC),
+            encoded_receipt,
+        );
+    }
+
+    fn handle_receipt(&mut self, data: &[u8]) -> libp2p::gossipsub::MessageAcceptance {
+        let Ok(receipt) = note::Signed::<note::Receipt>::decode(data) else {
+            return libp2p::gossipsub::MessageAcceptance::Reject;
+        };
+        if self.model.is_blocked(&receipt.pub_key) {
+            return libp2p::gossipsub::MessageAcceptance::Ignore;
+        }
+        // Counted by identity, so a note read on two devices is seen by one person.
+        let Some(identity) = receipt.verify_chain(&self.model.keys).cloned() else {
+            return libp2p::gossipsub::MessageAcceptance::Reject;
+        };
+
+        self.model
+            .receipts
+            .entry(receipt.inner.note_id)
+            .or_default()
+            .insert(identity);
+
+        libp2p::gossipsub::MessageAcceptance::Accept
+    }
+
+    fn handle_profile(&mut self, data: &[u8]) -> libp2p::gossipsub::MessageAcceptance {
+        let Ok(profile) = note::Signed::<note::Profile>::decode(data) else {
+            return libp2p::gossipsub::MessageAcceptance::Reject;
+        };
+        if !profile.verify() {
+            return libp2p::gossipsub::MessageAcceptance::Reject;
+        }
+        // A profile from the future would stay the newest, and could never be replaced.
+        let latest = time::OffsetDateTime::now_utc() + self.max_clock_skew;
+        if self.model.is_blocked(&profile.pub_key) || profile.inner.updated_at > latest {
+            return libp2p::gossipsub::MessageAcceptance::Ignore;
+        }
+
+        let pub_key = profile.pub_key.clone();
+        if self.model.add_profile(profile) {
+            self.save_profiles();
+            self.adopt_certificates(&pub_key);
+        }
+
+        libp2p::gossipsub::MessageAcceptance::Accept
+    }
+
+    fn handle_certificate(&mut self, data: &[u8]) -> libp2p::gossipsub::MessageAcceptance {
+        let Ok(certificate) = note::Certificate::decode(data) else {
+            return libp2p::gossipsub::MessageAcceptance::Reject;
+        };
+        if !certificate.verify() {
+            return libp2p::gossipsub::MessageAcceptance::Reject;
+        }
+
+        // Anyone can make keys, and certificates for them, by the thousand. Only those for keys we know are kept, so
+        // only those are passed on again. The others still spread now, so they reach whoever knows the key.
+        let known = match &certificate {
+            note::Certificate::Device(device) => {
+                self.model.knows(&device.pub_key) || self.model.knows(device.inner.device_key())
+            }
+            certificate => self.model.knows(certificate.pub_key()),
+        };
+        if !known {
+            if self.stray_certificates.len() >= MAX_STRAY_CERTIFICATES {
+                self.stray_certificates.pop_front();
+            }
+            self.stray_certificates.push_back(certificate);
+        } else if self.model.add_certificate(certificate) {
+            self.save_certificates();
+        }
+
+        libp2p::gossipsub::MessageAcceptance::Accept
+    }
+
+    /// Keeps the certificates that came in for the key before we knew it, see [`Controller::handle_certificate`].
+    fn adopt_certificates(&mut self, pub_key: &note::PubKey) {
+        let (adopted, strays): (VecDeque<_>, _) = std::mem::take(&mut self.stray_certificates)
+            .into_iter()
+            .partition(|certificate| match certificate {
+                note::Certificate::Device(device) => {
+                    device.pub_key == *pub_key || device.inner.device_key() == pub_key
+                }
+                certificate => certificate.pub_key() == pub_key,
+            });
+        self.stray_certificates = strays;
+
+        let mut changed = false;
+        for certificate in adopted {
+            changed |= self.model.add_certificate(certificate);
+        }
+        if changed {
+            self.save_certificates();
+        }
+    }
+
+    /// Peers stay in the model while we are connected to them or know where to find them.
+    fn forget_peer_if_unknown(&mut self, peer_id: libp2p::PeerId) {
+        if self
+            .model
+            .peers
+            .get(&peer_id)
+            .is_some_and(|peer| !peer.is_connected() && peer.addresses.is_empty())
+        {
+            self.model.peers.remove(&peer_id);
+        }
+    }
+
+    fn discover_topic_peers(&mut self) {
+        let key = topic_provider_key(GOSSIPSUB_TOPIC);
+        let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+
+        kademlia
+            .start_providing(key.clone())
+            .expect("failed to store provider record");
+        kademlia.get_providers(key);
+    }
+
+    pub fn model(&self) -> &model::Model {
+        &self.model
+    }
+
+    /// Keeps the key pair as our identity, to sign with from the next start on. Another identity we have is only
+    /// replaced with `replace`, it's gone for good unless it's backed up too.
+    pub fn restore_identity(&mut self, key_pair: &identity::Keypair, replace: bool) -> Restore {
+        let pub_key = note::PubKey::from(key_pair.public());
+        let result = self.store.load_identity().and_then(|current| {
+            if !replace && current.is_some_and(|current| current.public() != key_pair.public()) {
+                return Ok(Restore::Exists);
+            }
+            self.store
+                .save_identity(key_pair)
+                .map(|()| Restore::Restored)
+        });
+
+        self.model.status = Some(match &result {
+            Ok(Restore::Restored) => {
+                format!("Restored {}. Restart n2p to sign with it.", pub_key.short())
+            }
+            Ok(_) => "There is an identity already. Press Enter again to replace it.".to_string(),
+            Err(err) => format!("Failed to restore identity: {err}"),
+        });
+        result.unwrap_or(Restore::Failed)
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.model.status = Some(status);
+    }
+
+    pub fn local_peer_id(&self) -> libp2p::PeerId {
+        *self.swarm.local_peer_id()
+    }
+
+    pub fn listen_addrs(&self) -> Vec<libp2p::Multiaddr> {
+        self.swarm.listeners().cloned().collect()
+    }
+}
+
+/// Conversations are quiet, so peers aren't expected to deliver much. They mostly lose score by sending
+/// messages we reject, and a few of those are enough to get graylisted.
+fn peer_score_params() -> libp2p::gossipsub::PeerScoreParams {
+    let topic_params = libp2p::gossipsub::TopicScoreParams {
+        topic_weight: 1.0,
+        time_in_mesh_weight: 0.01,
+        time_in_mesh_quantum: std::time::Duration::from_secs(1),
+        time_in_mesh_cap: 3600.0,
+        first_message_deliveries_weight: 1.0,
+        first_message_deliveries_cap: 10.0,
+        mesh_message_deliveries_weight: 0.0,
+        mesh_failure_penalty_weight: 0.0,
+        invalid_message_deliveries_weight: -10.0,
+        invalid_message_deliveries_decay: 0.9,
+        ..Default::default()
+    };
+
+    libp2p::gossipsub::PeerScoreParams {
+        topics: [
+            GOSSIPSUB_TOPIC,
+            RECEIPTS_TOPIC,
+            PROFILES_TOPIC,
+            PARTS_TOPIC,
+            CERTIFICATES_TOPIC,
+        ]
+        .into_iter()
+        .map(|topic| {
+            (
+                libp2p::gossipsub::IdentTopic::new(topic).hash(),
+                topic_params.clone(),
+            )
+        })
+        .collect(),
+        ..Default::default()
+    }
+}
+
+/// Peers interested in a topic announce themselves as providers of this key in the DHT.
+fn topic_provider_key(topic: &str) -> libp2p::kad::RecordKey {
+    libp2p::kad::RecordKey::new(&sha3::Keccak256::digest(topic.as_bytes()).to_vec())
+}
+
+use crate::note::Decode as _;
+use crate::note::Encode as _;
+use crate::note::Sign as _;
+use crate::note::Signer as _;
+
+use anyhow::Context;
+use libp2p::futures::stream::FuturesUnordered;
+use libp2p::futures::StreamExt as _;
+use libp2p::identity;
+use libp2p::Transport as _;
+use sha3::Digest as _;
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::hash::Hash as _;
+use std::hash::Hasher as _;
+
+use crate::attachment;
+use crate::hlc;
+use crate::model;
+use crate::note;
+use crate::notify;
+use crate::parts;
+use crate::rate_limit;
+use crate::store;
+use crate::verify;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::relay;
+    use fake::Fake as _;
+    use rand::RngCore as _;
+    use rand::SeedableRng as _;
+
+    #[tokio::test]
+    async fn controllers_should_be_able_to_communicate() {
+        let mut c1 = Controller::new(Config::default()).unwrap();
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut secret_key_bytes = [0; 32];
+        rng.fill_bytes(&mut secret_key_bytes);
+        let keypair = identity::Keypair::ed25519_from_bytes(secret_key_bytes)
+            .expect("Failed to generate keypair");
+
+        // Faked clocks would put the note in the future, where peers refuse it.
+        let note = c1.compose_note(
+            fake::Faker.fake_with_rng(&mut rng),
+            fake::Faker.fake_with_rng(&mut rng),
+        );
+        let signed = note.sign(&keypair).expect("Failed to sign note");
+        let s1 = signed.clone();
+
+        for _ in 0..10 {
+            c1.poll().await;
+        }
+
+        let mut c2 = Controller::new(Config::default()).unwrap();
 
         for _ in 0..10 {
             c2.poll().await;
@@ -191,7 +1665,7 @@ mod tests {
             for _ in 0..149 {
                 c1.poll().await;
             }
-            c1.send_note(s1);
+            c1.send_note(s1).unwrap();
 
             c1.poll().await;
 
@@ -212,6 +1686,786 @@ mod tests {
         let m1 = h1.await.expect("oh noooo");
         let m2 = h2.await.expect("oh noooo");
 
-        assert_eq!(m1, m2);
+        assert_eq!(m1.topics, m2.topics);
+    }
+
+    #[tokio::test]
+    async fn controllers_should_find_each_other_through_the_dht() {
+        let mut bootstrap = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let bootstrap_addr = dialable_addr(&mut bootstrap).await;
+
+        let mut peers: Vec<_> = (0..3)
+            .map(|_| Controller::new(dht_only_config(vec![bootstrap_addr.clone()])).unwrap())
+            .collect();
+
+        let all_connected = |peers: &[Controller]| {
+            peers.iter().all(|peer| {
+                peers.iter().all(|other| {
+                    peer.local_peer_id() == other.local_peer_id()
+                        || peer.swarm.is_connected(&other.local_peer_id())
+                })
+            })
+        };
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while !all_connected(&peers) {
+                tokio::select! {
+                    _ = bootstrap.poll() => {}
+                    _ = futures::future::select_all(peers.iter_mut().map(|peer| Box::pin(peer.poll()))) => {}
+                }
+            }
+        })
+        .await
+        .expect("peers did not find each other through the DHT");
+    }
+
+    #[tokio::test]
+    async fn controllers_should_reach_each_other_through_a_relay() {
+        let mut relay = relay::Relay::new(relay::Config {
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            external_addrs: Vec::new(),
+            swarm_key: None,
+            key: identity::Keypair::generate_ed25519(),
+        })
+        .unwrap();
+        while relay.listen_addrs().is_empty() {
+            relay.poll().await;
+        }
+        let relay_addr = relay.listen_addrs()[0]
+            .clone()
+            .with(libp2p::multiaddr::Protocol::P2p(relay.local_peer_id()));
+
+        // Neither controller listens directly, so a relayed connection is the only way to connect.
+        let mut c1 = Controller::new(Config {
+            relays: vec![relay_addr],
+            listen_addrs: Vec::new(),
+            ..dht_only_config(Vec::new())
+        })
+        .unwrap();
+        let mut c2 = Controller::new(Config {
+            listen_addrs: Vec::new(),
+            ..dht_only_config(Vec::new())
+        })
+        .unwrap();
+        let c1_peer_id = c1.local_peer_id();
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while c1.listen_addrs().is_empty() {
+                tokio::select! {
+                    _ = relay.poll() => {}
+                    _ = c1.poll() => {}
+                }
+            }
+
+            // Circuit addresses already end with our peer id.
+            c2.swarm.dial(c1.listen_addrs()[0].clone()).unwrap();
+
+            while !c2.swarm.is_connected(&c1_peer_id) {
+                tokio::select! {
+                    _ = relay.poll() => {}
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("controllers did not connect through the relay");
+    }
+
+    #[tokio::test]
+    async fn only_controllers_with_the_same_swarm_key_should_connect() {
+        let private_config = |key: [u8; 32]| Config {
+            swarm_key: Some(libp2p::pnet::PreSharedKey::new(key)),
+            ..dht_only_config(Vec::new())
+        };
+
+        let mut c1 = Controller::new(private_config([1; 32])).unwrap();
+        let c1_addr = dialable_addr(&mut c1).await;
+        let c1_peer_id = c1.local_peer_id();
+        let mut c2 = Controller::new(private_config([1; 32])).unwrap();
+        let mut outsider = Controller::new(private_config([2; 32])).unwrap();
+
+        c2.swarm.dial(c1_addr.clone()).unwrap();
+        outsider.swarm.dial(c1_addr).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while !c2.swarm.is_connected(&c1_peer_id) || outsider.model.status.is_none() {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                    _ = outsider.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("controllers did not connect or fail as expected");
+
+        assert!(!outsider.swarm.is_connected(&c1_peer_id));
+    }
+
+    #[tokio::test]
+    async fn controllers_should_track_connected_peers() {
+        let mut c1 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let c1_addr = dialable_addr(&mut c1).await;
+        let c1_peer_id = c1.local_peer_id();
+        let mut c2 = Controller::new(dht_only_config(Vec::new())).unwrap();
+
+        c2.swarm.dial(c1_addr).unwrap();
+
+        let is_tracked = |peer: Option<&model::Peer>| {
+            peer.is_some_and(|peer| {
+                peer.agent_version.is_some()
+                    && peer.rtt.is_some()
+                    && peer.topics.contains(GOSSIPSUB_TOPIC)
+            })
+        };
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while !is_tracked(c2.model.peers.get(&c1_peer_id)) {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("peer details were never filled in");
+
+        let peer = &c2.model.peers[&c1_peer_id];
+        assert!(peer.is_connected());
+        assert_eq!(peer.transport, Some(model::Transport::Tcp));
+        assert_eq!(
+            peer.agent_version.as_deref(),
+            Some(concat!("n2p/", env!("CARGO_PKG_VERSION")))
+        );
+    }
+
+    #[tokio::test]
+    async fn notes_sent_while_alone_should_be_published_when_a_peer_subscribes() {
+        let signed = fake_signed_note(42);
+        let id = signed.id();
+
+        let mut c1 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        c1.send_note(signed).unwrap();
+
+        assert_eq!(c1.model.delivery(&id), Some(model::Delivery::Pending));
+
+        let c1_addr = dialable_addr(&mut c1).await;
+        let mut c2 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        c2.swarm.dial(c1_addr).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while c2.model.topics != c1.model.topics {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("pending note was never delivered");
+
+        assert_eq!(c1.model.delivery(&id), Some(model::Delivery::Delivered));
+        assert!(c1.model.outbox.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pending_notes_should_survive_a_restart() {
+        let signed = fake_signed_note(42);
+        let data_dir = tempfile::tempdir().expect("failed to create temp dir");
+        let config = Config {
+            data_dir: Some(data_dir.path().to_path_buf()),
+            ..dht_only_config(Vec::new())
+        };
+
+        let mut c1 = Controller::new(config.clone()).unwrap();
+        c1.send_note(signed.clone()).unwrap();
+        drop(c1);
+
+        let restarted = Controller::new(config).unwrap();
+
+        assert_eq!(
+            restarted.model.delivery(&signed.id()),
+            Some(model::Delivery::Pending)
+        );
+        assert_eq!(
+            restarted.model.topics[&signed.inner.topic].notes[&(signed.inner.hlc, signed.id())],
+            signed
+        );
+    }
+
+    #[tokio::test]
+    async fn authors_should_get_receipts_from_peers_that_did_not_opt_out() {
+        let signed = fake_signed_note(42);
+        let id = signed.id();
+
+        let mut author = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let author_addr = dialable_addr(&mut author).await;
+        let mut private = Controller::new(Config {
+            send_receipts: false,
+            ..dht_only_config(Vec::new())
+        })
+        .unwrap();
+        let mut reader = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let reader_pub_key: note::PubKey = reader.signing_key.public().into();
+
+        private.swarm.dial(author_addr.clone()).unwrap();
+        reader.swarm.dial(author_addr).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            let subscribers = |c: &Controller| {
+                c.model
+                    .peers
+                    .values()
+                    .filter(|peer| peer.topics.contains(GOSSIPSUB_TOPIC))
+                    .count()
+            };
+            while subscribers(&author) < 2 {
+                tokio::select! {
+                    _ = author.poll() => {}
+                    _ = private.poll() => {}
+                    _ = reader.poll() => {}
+                }
+            }
+
+            author.send_note(signed).unwrap();
+
+            while author.model.seen_by(&id) == 0 || private.model.topics != author.model.topics {
+                tokio::select! {
+                    _ = author.poll() => {}
+                    _ = private.poll() => {}
+                    _ = reader.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("author never got a receipt");
+
+        assert_eq!(
+            author.model.receipts[&id],
+            std::collections::BTreeSet::from([reader_pub_key])
+        );
+    }
+
+    #[tokio::test]
+    async fn profiles_should_reach_peers_that_subscribe_later() {
+        let mut c1 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let c1_addr = dialable_addr(&mut c1).await;
+        let c1_pub_key: note::PubKey = c1.signing_key.public().into();
+        c1.set_profile(note::Profile {
+            display_name: "Alice".to_string(),
+            status: "Out for lunch".to_string(),
+            avatar_hash: None,
+            updated_at: time::OffsetDateTime::now_utc(),
+        });
+
+        let mut c2 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        c2.swarm.dial(c1_addr).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while !c2.model.profiles.contains_key(&c1_pub_key) {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("profile never arrived");
+
+        assert_eq!(c2.model.author_name(&c1_pub_key), "~Alice");
+        assert_eq!(c2.model.profiles[&c1_pub_key].inner.status, "Out for lunch");
+    }
+
+    #[tokio::test]
+    async fn certificates_should_be_kept_once_their_key_shows_up() {
+        let key_pair = identity::Keypair::generate_ed25519();
+        let pub_key: note::PubKey = key_pair.public().into();
+        let revocation = note::Certificate::Revocation(
+            note::Revocation {
+                revoked_at: time::OffsetDateTime::now_utc(),
+                reason: String::new(),
+            }
+            .sign(&key_pair)
+            .unwrap(),
+        );
+        let mut c = Controller::new(dht_only_config(Vec::new())).unwrap();
+
+        assert!(matches!(
+            c.handle_certificate(&revocation.encode_to_vec().unwrap()),
+            libp2p::gossipsub::MessageAcceptance::Accept
+        ));
+        assert!(c.model.keys.certificates().is_empty());
+
+        let profile = note::Profile {
+            display_name: "Alice".to_string(),
+            status: String::new(),
+            avatar_hash: None,
+            updated_at: time::OffsetDateTime::now_utc(),
+        }
+        .sign(&key_pair)
+        .unwrap();
+        c.handle_profile(&profile.encode_to_vec().unwrap());
+        assert_eq!(c.model.keys.certificates(), vec![revocation]);
+        assert!(c.model.keys.revoked_at(&pub_key).is_some());
+    }
+
+    #[tokio::test]
+    async fn receipts_from_blocked_authors_should_be_ignored() {
+        let key_pair = identity::Keypair::generate_ed25519();
+        let note_id = fake_signed_note(42).id();
+        let receipt = note::Receipt { note_id }.sign(&key_pair).unwrap();
+        let mut c = Controller::new(dht_only_config(Vec::new())).unwrap();
+        c.block_author(key_pair.public().into());
+
+        assert!(matches!(
+            c.handle_receipt(&receipt.encode_to_vec().unwrap()),
+            libp2p::gossipsub::MessageAcceptance::Ignore
+        ));
+        assert_eq!(c.model.seen_by(&note_id), 0);
+    }
+
+    #[tokio::test]
+    async fn restoring_should_not_replace_another_identity_unasked() {
+        let dir = tempfile::tempdir().unwrap();
+        let restored = identity::Keypair::generate_ed25519();
+
+        let mut c = Controller::new(dht_only_config(Vec::new())).unwrap();
+        assert_eq!(c.restore_identity(&restored, true), Restore::Failed);
+
+        let store = store::Store::open(dir.path()).unwrap();
+        let current = store.load_or_create_identity().unwrap();
+        let mut c = Controller::new(Config {
+            data_dir: Some(dir.path().to_path_buf()),
+            ..dht_only_config(Vec::new())
+        })
+        .unwrap();
+        assert_eq!(c.restore_identity(&current, false), Restore::Restored);
+        assert_eq!(c.restore_identity(&restored, false), Restore::Exists);
+        assert_eq!(
+            store
+                .load_identity()
+                .unwrap()
+                .map(|key_pair| key_pair.public()),
+            Some(current.public())
+        );
+        assert_eq!(c.restore_identity(&restored, true), Restore::Restored);
+        assert_eq!(
+            store
+                .load_identity()
+                .unwrap()
+                .map(|key_pair| key_pair.public()),
+            Some(restored.public())
+        );
+    }
+
+    #[tokio::test]
+    async fn revocations_should_reach_peers_that_subscribe_later() {
+        let mut c1 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let c1_addr = dialable_addr(&mut c1).await;
+        let c1_pub_key: note::PubKey = c1.signing_key.public().into();
+        let revoked_at = time::OffsetDateTime::now_utc();
+        let revocation = note::Revocation {
+            revoked_at,
+            reason: "Laptop was stolen".to_string(),
+        }
+        .sign(&c1.signing_key)
+        .unwrap();
+        c1.certify(note::Certificate::Revocation(revocation));
+
+        let mut c2 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        // Certificates are only kept for keys we know.
+        c2.update_contact(c1_pub_key.clone(), |contact| {
+            contact.petname = "Alice".to_string()
+        });
+        c2.swarm.dial(c1_addr).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while c2.model.keys.revoked_at(&c1_pub_key).is_none() {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("revocation never arrived");
+
+        assert_eq!(c2.model.keys.revoked_at(&c1_pub_key), Some(revoked_at));
+    }
+
+    #[tokio::test]
+    async fn attachments_should_be_fetched_from_peers_in_chunks() {
+        let c1_dir = tempfile::tempdir().unwrap();
+        let c2_dir = tempfile::tempdir().unwrap();
+        let downloads = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..attachment::CHUNK_SIZE * 2 + 1000)
+            .map(|i| (i % 251) as u8)
+            .collect();
+
+        let mut c1 = Controller::new(Config {
+            data_dir: Some(c1_dir.path().to_path_buf()),
+            ..dht_only_config(Vec::new())
+        })
+        .unwrap();
+        let c1_addr = dialable_addr(&mut c1).await;
+        let attachment = c1.attach("notes.txt".to_string(), &data).unwrap();
+
+        let mut c2 = Controller::new(Config {
+            data_dir: Some(c2_dir.path().to_path_buf()),
+            download_dir: downloads.path().to_path_buf(),
+            ..dht_only_config(Vec::new())
+        })
+        .unwrap();
+        c2.swarm.dial(c1_addr).unwrap();
+        wait_for_subscriber(&mut c1, &mut c2).await;
+
+        async fn fetch(c1: &mut Controller, c2: &mut Controller, attachment: &note::Attachment) {
+            c2.model.transfers.remove(&attachment.root);
+            c2.save_attachment(attachment.clone());
+            tokio::time::timeout(std::time::Duration::from_secs(60), async {
+                while !matches!(
+                    c2.model.transfers.get(&attachment.root),
+                    Some(model::Transfer::Saved(_) | model::Transfer::Failed)
+                ) {
+                    tokio::select! {
+                        _ = c1.poll() => {}
+                        _ = c2.poll() => {}
+                    }
+                }
+            })
+            .await
+            .expect("attachment never arrived");
+        }
+
+        // Until it's in a note, nobody is handed the file.
+        fetch(&mut c1, &mut c2, &attachment).await;
+        assert_eq!(
+            c2.model.transfers[&attachment.root],
+            model::Transfer::Failed
+        );
+
+        let mut note = c1.compose_note("files".to_string(), String::new());
+        note.attachments = vec![attachment.clone()];
+        let signed = note.sign(&c1.signing_key).unwrap();
+        c1.send_note(signed).unwrap();
+        fetch(&mut c1, &mut c2, &attachment).await;
+
+        let saved = downloads.path().join("notes.txt");
+        assert_eq!(
+            c2.model.transfers[&attachment.root],
+            model::Transfer::Saved(saved.clone())
+        );
+        assert_eq!(std::fs::read(saved).unwrap(), data);
+        assert_eq!(attachment.mime_type, "text/plain");
+        assert_eq!(attachment.size, data.len() as u64);
+
+        // A note that lies about the size doesn't get the file saved.
+        let wrong_size = note::Attachment {
+            size: attachment.size - 1,
+            ..attachment.clone()
+        };
+        fetch(&mut c1, &mut c2, &wrong_size).await;
+        assert_eq!(
+            c2.model.transfers[&attachment.root],
+            model::Transfer::Failed
+        );
+        assert_eq!(std::fs::read_dir(downloads.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn long_notes_should_arrive_in_parts() {
+        let config = Config {
+            max_message_size: 1024,
+            ..dht_only_config(Vec::new())
+        };
+        let mut c1 = Controller::new(config.clone()).unwrap();
+        let c1_addr = dialable_addr(&mut c1).await;
+        let mut c2 = Controller::new(Config {
+            signing_key: identity::Keypair::generate_ed25519().into(),
+            ..config
+        })
+        .unwrap();
+        c2.swarm.dial(c1_addr).unwrap();
+        wait_for_subscriber(&mut c1, &mut c2).await;
+
+        // Random enough that compression doesn't make it fit after all.
+        let msg = (0..64u32)
+            .map(|i| format!("{:x}", sha3::Keccak256::digest(i.to_le_bytes())))
+            .collect();
+        let note = c1.compose_note("paste".to_string(), msg);
+        let signed = note.sign(&c1.signing_key).unwrap();
+        c1.send_note(signed.clone()).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while !c2.model.contains(&signed.id()) {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("long note never arrived");
+    }
+
+    #[tokio::test]
+    async fn notes_from_blocked_authors_should_be_dropped() {
+        let blocked = fake_signed_note(42);
+        let allowed = fake_signed_note(43);
+
+        let mut c1 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let c1_addr = dialable_addr(&mut c1).await;
+        let mut c2 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        c2.block_author(blocked.pub_key.clone());
+        c2.swarm.dial(c1_addr).unwrap();
+        wait_for_subscriber(&mut c1, &mut c2).await;
+
+        // Notes arrive in the order they were sent, so once the allowed one is there, the blocked one came and went.
+        c1.send_note(blocked).unwrap();
+        c1.send_note(allowed.clone()).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while !c2.model.topics.contains_key(&allowed.inner.topic) {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("allowed note never arrived");
+
+        let received: Vec<_> = c2
+            .model
+            .topics
+            .values()
+            .flat_map(|topic| topic.notes.values())
+            .collect();
+        assert_eq!(received, vec![&allowed]);
+    }
+
+    #[tokio::test]
+    async fn blocked_peers_should_be_disconnected_and_refused() {
+        let mut c1 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let c1_addr = dialable_addr(&mut c1).await;
+        let mut c2 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let c2_id = c2.local_peer_id();
+        c2.swarm.dial(c1_addr.clone()).unwrap();
+
+        let connected = |c: &Controller| {
+            c.model
+                .peers
+                .get(&c2_id)
+                .is_some_and(model::Peer::is_connected)
+        };
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while !connected(&c1) {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+
+            c1.block_peer(c2_id);
+
+            while connected(&c1) {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("blocked peer was never disconnected");
+
+        c2.swarm.dial(c1_addr).unwrap();
+        let _ = tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            loop {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+                assert!(!connected(&c1), "blocked peer got through");
+            }
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn notes_from_the_future_should_be_refused() {
+        let key_pair = identity::Keypair::generate_ed25519();
+        let note_at = |created_at| {
+            note::tests::note_with("Derp", "Hello", created_at)
+                .sign(&key_pair)
+                .expect("failed to sign note")
+        };
+        let now = time::OffsetDateTime::now_utc();
+        let future = note_at(now + time::Duration::hours(1));
+        let present = note_at(now);
+
+        let mut c1 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let c1_addr = dialable_addr(&mut c1).await;
+        let mut c2 = Controller::new(dht_only_config(Vec::new())).unwrap();
+        c2.swarm.dial(c1_addr).unwrap();
+        wait_for_subscriber(&mut c1, &mut c2).await;
+
+        c1.send_note(future).unwrap();
+        c1.send_note(present.clone()).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while !c2.model.topics.contains_key("Derp") {
+                tokio::select! {
+                    _ = c1.poll() => {}
+                    _ = c2.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("note from the present never arrived");
+
+        let received: Vec<_> = c2.model.topics["Derp"].notes.values().collect();
+        assert_eq!(received, vec![&present]);
+    }
+
+    #[tokio::test]
+    async fn profiles_from_the_future_should_be_ignored() {
+        let key_pair = identity::Keypair::generate_ed25519();
+        let profile_at = |updated_at| {
+            note::Profile {
+                display_name: "Alice".to_string(),
+                status: String::new(),
+                avatar_hash: None,
+                updated_at,
+            }
+            .sign(&key_pair)
+            .expect("failed to sign profile")
+            .encode_to_vec()
+            .expect("failed to encode profile")
+        };
+        let now = time::OffsetDateTime::now_utc();
+        let mut c = Controller::new(dht_only_config(Vec::new())).unwrap();
+
+        assert!(matches!(
+            c.handle_profile(&profile_at(now + time::Duration::days(365))),
+            libp2p::gossipsub::MessageAcceptance::Ignore
+        ));
+        assert!(matches!(
+            c.handle_profile(&profile_at(now)),
+            libp2p::gossipsub::MessageAcceptance::Accept
+        ));
+        assert_eq!(c.model.author_name(&key_pair.public().into()), "~Alice");
+    }
+
+    #[tokio::test]
+    async fn flooding_peers_should_get_graylisted() {
+        let mut flooder = Controller::new(dht_only_config(Vec::new())).unwrap();
+        let flooder_addr = dialable_addr(&mut flooder).await;
+        let flooder_id = flooder.local_peer_id();
+        let mut victim = Controller::new(Config {
+            peer_rate_limit: rate_limit::RateLimit {
+                burst: 5,
+                interval: std::time::Duration::from_secs(3600),
+            },
+            ..dht_only_config(Vec::new())
+        })
+        .unwrap();
+        victim.swarm.dial(flooder_addr).unwrap();
+
+        let graylist_threshold =
+            libp2p::gossipsub::PeerScoreThresholds::default().graylist_threshold;
+        let score = |c: &Controller| {
+            c.swarm
+                .behaviour()
+                .gossipsub
+                .peer_score(&flooder_id)
+                .unwrap_or_default()
+        };
+
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            wait_for_subscriber(&mut flooder, &mut victim).await;
+
+            // Different authors, so only the peer limit applies.
+            for seed in 0..30 {
+                flooder.send_note(fake_signed_note(seed)).unwrap();
+            }
+
+            while score(&victim) >= graylist_threshold {
+                tokio::select! {
+                    _ = flooder.poll() => {}
+                    _ = victim.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("flooder was never graylisted");
+
+        let received = victim
+            .model
+            .topics
+            .values()
+            .map(|topic| topic.notes.len())
+            .sum::<usize>();
+        assert!(received <= 5, "took {received} notes from the flooder");
+    }
+
+    /// Once `author` knows `reader` listens, notes are published right away, in the order they are sent.
+    async fn wait_for_subscriber(author: &mut Controller, reader: &mut Controller) {
+        let reader_id = reader.local_peer_id();
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while !author
+                .model
+                .peers
+                .get(&reader_id)
+                .is_some_and(|peer| peer.topics.contains(GOSSIPSUB_TOPIC))
+            {
+                tokio::select! {
+                    _ = author.poll() => {}
+                    _ = reader.poll() => {}
+                }
+            }
+        })
+        .await
+        .expect("reader never subscribed");
+    }
+
+    fn fake_signed_note(seed: u64) -> note::Signed<note::Note> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut secret_key_bytes = [0; 32];
+        rng.fill_bytes(&mut secret_key_bytes);
+        let keypair = identity::Keypair::ed25519_from_bytes(secret_key_bytes)
+            .expect("Failed to generate keypair");
+
+        let mut note: note::Note = fake::Faker.fake_with_rng(&mut rng);
+        // Peers refuse notes from the future.
+        note.created_at = note.created_at.min(time::OffsetDateTime::now_utc());
+        note.hlc = note
+            .hlc
+            .min(hlc::Timestamp::from_wall_clock(note.created_at));
+        note.sign(&keypair).expect("Failed to sign note")
+    }
+
+    fn dht_only_config(bootstrap_peers: Vec<libp2p::Multiaddr>) -> Config {
+        Config {
+            mdns: false,
+            bootstrap_peers,
+            relays: Vec::new(),
+            swarm_key: None,
+            data_dir: None,
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            discovery_interval: std::time::Duration::from_millis(500),
+            ..Default::default()
+        }
+    }
+
+    async fn dialable_addr(controller: &mut Controller) -> libp2p::Multiaddr {
+        while controller.listen_addrs().is_empty() {
+            controller.poll().await;
+        }
+
+        controller.listen_addrs()[0]
+            .clone()
+            .with(libp2p::multiaddr::Protocol::P2p(controller.local_peer_id()))
     }
 }
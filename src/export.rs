@@ -0,0 +1,270 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Notes with their keys and signatures in base64, for other tools
+    Json,
+    /// A transcript for people to read
+    Markdown,
+    /// Raw signed notes, which n2p can import and verify
+    Bundle,
+}
+
+/// Writes one topic, or all of them, in conversation order. Times in transcripts are in `local_offset`.
+pub fn export(
+    model: &model::Model,
+    topic: Option<&str>,
+    format: Format,
+    local_offset: time::UtcOffset,
+    mut writer: impl io::Write,
+) -> Result<(), Error> {
+    let topics: Vec<(&str, &model::Topic)> = match topic {
+        Some(name) => vec![(
+            name,
+            model
+                .topics
+                .get(name)
+                .ok_or_else(|| Error::UnknownTopic(name.to_string()))?,
+        )],
+        None => model
+            .topics
+            .iter()
+            .map(|(name, topic)| (name.as_str(), topic))
+            .collect(),
+    };
+
+    match format {
+        Format::Json => write_json(model, &topics, &mut writer)?,
+        Format::Markdown => write_markdown(model, &topics, local_offset, &mut writer)?,
+        Format::Bundle => bundle::write(
+            &mut writer,
+            topics.iter().flat_map(|(_, topic)| topic.ordered()),
+        )?,
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct JsonExport<'a> {
+    topics: Vec<JsonTopic<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonTopic<'a> {
+    name: &'a str,
+    notes: Vec<JsonNote<'a>>,
+}
+
+/// `signature` signs the Keccak256 digest of `signed_content`, the note's [canonical](crate::canonical) encoding, so
+/// notes can be verified without n2p.
+#[derive(serde::Serialize)]
+struct JsonNote<'a> {
+    id: String,
+    author: String,
+    /// Protobuf encoded libp2p public key.
+    pub_key: String,
+    created_at: String,
+    hlc: hlc::Timestamp,
+    parents: Vec<String>,
+    msg: &'a str,
+    signed_content: String,
+    signature: String,
+}
+
+fn write_json(
+    model: &model::Model,
+    topics: &[(&str, &model::Topic)],
+    writer: impl io::Write,
+) -> Result<(), Error> {
+    let topics = topics
+        .iter()
+        .map(|(name, topic)| {
+            let notes = topic
+                .ordered()
+                .into_iter()
+                .map(|note| json_note(model, note))
+                .collect::<Result<_, Error>>()?;
+            Ok(JsonTopic { name, notes })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    serde_json::to_writer_pretty(writer, &JsonExport { topics })?;
+    Ok(())
+}
+
+fn json_note<'a>(
+    model: &model::Model,
+    note: &'a note::Signed<note::Note>,
+) -> Result<JsonNote<'a>, Error> {
+    Ok(JsonNote {
+        id: note.id().to_string(),
+        author: model.author_name(&note.pub_key),
+        pub_key: BASE64_STANDARD.encode(Vec::<u8>::from(note.pub_key.clone())),
+        created_at: note
+            .inner
+            .created_at
+            .format(&time::format_description::well_known::Rfc3339)?,
+        hlc: note.inner.hlc,
+        parents: note.inner.parents.iter().map(ToString::to_string).collect(),
+        msg: &note.inner.msg,
+        signed_content: BASE64_STANDARD.encode(canonical::signing_bytes(&note.inner)),
+        signature: BASE64_STANDARD.encode(&note.signature),
+    })
+}
+
+fn write_markdown(
+    model: &model::Model,
+    topics: &[(&str, &model::Topic)],
+    local_offset: time::UtcOffset,
+    mut writer: impl io::Write,
+) -> Result<(), Error> {
+    for (name, topic) in topics {
+        writeln!(writer, "# {name}")?;
+
+        for note in topic.ordered() {
+            let created_at = note.inner.created_at.to_offset(local_offset);
+            writeln!(writer)?;
+            writeln!(
+                writer,
+                "**{}** · {} {:02}:{:02}",
+                model.author_name(&note.pub_key),
+                created_at.date(),
+                created_at.hour(),
+                created_at.minute()
+            )?;
+            writeln!(writer)?;
+            for line in note.inner.msg.lines() {
+                writeln!(writer, "> {line}")?;
+            }
+        }
+
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no topic named {0}")]
+    UnknownTopic(String),
+    #[error("io error")]
+    Io(#[from] io::Error),
+    #[error("encoding error")]
+    Encoding(#[from] bincode::Error),
+    #[error("json error")]
+    Json(#[from] serde_json::Error),
+    #[error("time formatting error")]
+    Time(#[from] time::error::Format),
+    #[error("bundle error")]
+    Bundle(#[from] bundle::Error),
+}
+
+use std::io;
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine as _;
+
+use crate::bundle;
+use crate::canonical;
+use crate::hlc;
+use crate::model;
+use crate::note;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use note::Sign as _;
+    use sha3::Digest as _;
+
+    fn model_with_notes() -> (model::Model, libp2p::identity::Keypair) {
+        let key_pair = libp2p::identity::Keypair::generate_ed25519();
+        let mut model = model::Model::new();
+        for (minute, msg) in [(0, "Shall we ship it?"), (1, "Ship it.\nToday.")] {
+            let created_at =
+                time::macros::datetime!(2024-05-10 12:00 UTC) + time::Duration::minutes(minute);
+            let note = note::tests::note_with("ops", msg, created_at)
+                .sign(&key_pair)
+                .expect("failed to sign note");
+            model.add_note(note);
+        }
+        model.contacts.insert(
+            key_pair.public().into(),
+            model::Contact {
+                petname: "Alice".to_string(),
+                ..Default::default()
+            },
+        );
+
+        (model, key_pair)
+    }
+
+    #[test]
+    fn json_exports_should_be_verifiable_without_n2p() {
+        let (model, key_pair) = model_with_notes();
+
+        let mut json = Vec::new();
+        export(
+            &model,
+            Some("ops"),
+            Format::Json,
+            time::UtcOffset::UTC,
+            &mut json,
+        )
+        .expect("failed to export");
+        let json: serde_json::Value = serde_json::from_slice(&json).expect("invalid json");
+
+        let notes = json["topics"][0]["notes"].as_array().expect("no notes");
+        assert_eq!(notes.len(), 2);
+        for note in notes {
+            let decode = |field: &str| {
+                BASE64_STANDARD
+                    .decode(note[field].as_str().expect("missing field"))
+                    .expect("invalid base64")
+            };
+            let pub_key = libp2p::identity::PublicKey::try_decode_protobuf(&decode("pub_key"))
+                .expect("invalid public key");
+            let digest = sha3::Keccak256::digest(decode("signed_content"));
+
+            assert_eq!(pub_key, key_pair.public());
+            assert!(pub_key.verify(&digest, &decode("signature")));
+            assert_eq!(note["author"], "Alice");
+        }
+    }
+
+    #[test]
+    fn markdown_exports_should_read_like_a_transcript() {
+        let (model, _) = model_with_notes();
+
+        let mut markdown = Vec::new();
+        export(
+            &model,
+            None,
+            Format::Markdown,
+            time::UtcOffset::UTC,
+            &mut markdown,
+        )
+        .expect("failed to export");
+
+        assert_eq!(
+            String::from_utf8(markdown).expect("invalid utf-8"),
+            "# ops\n\n\
+             **Alice** · 2024-05-10 12:00\n\n\
+             > Shall we ship it?\n\n\
+             **Alice** · 2024-05-10 12:01\n\n\
+             > Ship it.\n\
+             > Today.\n\n"
+        );
+    }
+
+    #[test]
+    fn exporting_an_unknown_topic_should_fail() {
+        let (model, _) = model_with_notes();
+
+        assert!(matches!(
+            export(&model, Some("nope"), Format::Bundle, time::UtcOffset::UTC, io::sink()),
+            Err(Error::UnknownTopic(topic)) if topic == "nope"
+        ));
+    }
+}
@@ -0,0 +1,106 @@
+/// Hybrid logical clock timestamp: wall clock milliseconds, plus a counter for events the wall clock can't tell apart.
+///
+/// A note stamped after its author saw another note is always stamped later, however far their clocks drift.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[cfg_attr(test, derive(fake::Dummy))]
+pub struct Timestamp {
+    pub millis: u64,
+    pub counter: u32,
+}
+
+impl canonical::Canonical for Timestamp {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self { millis, counter } = self;
+        millis.write_canonical(out);
+        counter.write_canonical(out);
+    }
+}
+
+impl Timestamp {
+    pub fn from_wall_clock(now: time::OffsetDateTime) -> Self {
+        Self {
+            millis: (now.unix_timestamp_nanos() / 1_000_000).max(0) as u64,
+            counter: 0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Clock {
+    last: Timestamp,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stamps an event of ours, later than anything we stamped or saw before.
+    pub fn tick(&mut self, now: time::OffsetDateTime) -> Timestamp {
+        let now = Timestamp::from_wall_clock(now);
+        self.last = if now.millis > self.last.millis {
+            now
+        } else {
+            Timestamp {
+                millis: self.last.millis,
+                counter: self.last.counter.saturating_add(1),
+            }
+        };
+        self.last
+    }
+
+    /// Makes sure whatever we stamp next comes after `remote`.
+    pub fn observe(&mut self, remote: Timestamp) {
+        self.last = self.last.max(remote);
+    }
+}
+
+use crate::canonical;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_should_come_after_observed_timestamps_from_the_future() {
+        let now = time::OffsetDateTime::now_utc();
+        let remote = Timestamp::from_wall_clock(now + time::Duration::minutes(1));
+        let mut clock = Clock::new();
+
+        clock.observe(remote);
+
+        let first = clock.tick(now);
+        let second = clock.tick(now);
+
+        assert!(remote < first);
+        assert!(first < second);
+    }
+
+    #[test]
+    fn ticks_should_follow_the_wall_clock_when_it_is_ahead() {
+        let now = time::OffsetDateTime::now_utc();
+        let mut clock = Clock::new();
+
+        let first = clock.tick(now);
+        let second = clock.tick(now);
+        let later = clock.tick(now + time::Duration::seconds(1));
+
+        assert!(first < second);
+        assert_eq!(
+            later,
+            Timestamp::from_wall_clock(now + time::Duration::seconds(1))
+        );
+    }
+}
@@ -0,0 +1,233 @@
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    pub accepted: usize,
+    /// Notes we already had, or that were in the bundle more than once.
+    pub duplicates: usize,
+    /// Notes that didn't decode or whose signature didn't verify.
+    pub invalid: usize,
+    /// Notes by authors we blocked.
+    pub blocked: usize,
+    /// Notes dated further ahead than clocks may be off, which we'd refuse from peers too.
+    pub future: usize,
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} accepted, {} duplicate, {} invalid",
+            self.accepted, self.duplicates, self.invalid
+        )?;
+        if self.blocked > 0 {
+            write!(f, ", {} blocked", self.blocked)?;
+        }
+        if self.future > 0 {
+            write!(f, ", {} from the future", self.future)?;
+        }
+        Ok(())
+    }
+}
+
+/// Merges the notes of a bundle into the store. With `republish`, they are also queued in the outbox, so they are
+/// published the next time we are online. Bundles from before the canonical signing encoding still import, but their
+/// notes aren't republished, as peers would refuse their signatures.
+///
+/// Notes dated more than `max_clock_skew` ahead are left out, like they are when they come from peers.
+pub fn import(
+    store: &store::Store,
+    reader: impl io::Read,
+    republish: bool,
+    max_clock_skew: std::time::Duration,
+) -> Result<Report, Error> {
+    let model = store.load_model()?;
+    let latest = time::OffsetDateTime::now_utc() + max_clock_skew;
+    let mut seen = BTreeSet::new();
+    let mut accepted = Vec::new();
+    let mut report = Report::default();
+
+    let mut notes = Vec::new();
+    for record in bundle::read(reader)? {
+        let record = match record {
+            Ok(record) => record,
+            // Where the broken record ends is anyone's guess, so it's the last one we can read.
+            Err(bundle::Error::Truncated | bundle::Error::RecordTooLarge) => {
+                report.invalid += 1;
+                break;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        match note::Signed::<note::Note>::decode_compressed(&record) {
+            Ok(note) => notes.push(note),
+            Err(_) => report.invalid += 1,
+        }
+    }
+
+    for batch in notes.chunks(verify::MAX_BATCH_SIZE) {
+        for (note, valid) in batch.iter().zip(verify::verify_batch(batch)) {
+            let legacy = !valid;
+            if legacy && !note.verify_legacy() {
+                report.invalid += 1;
+                continue;
+            }
+
+            let id = note.id();
+            if model.contains(&id) || !seen.insert(id) {
+                report.duplicates += 1;
+            } else if model.is_blocked(&note.pub_key) {
+                report.blocked += 1;
+            } else if note.inner.is_after(latest) {
+                report.future += 1;
+            } else {
+                accepted.push((note.clone(), legacy));
+            }
+        }
+    }
+
+    store.append_notes(accepted.iter().map(|(note, _)| note))?;
+    if republish {
+        let mut outbox = store.load_outbox()?;
+        outbox.extend(
+            accepted
+                .iter()
+                .filter(|(_, legacy)| !legacy)
+                .map(|(note, _)| note.clone()),
+        );
+        store.save_outbox(&outbox)?;
+    }
+
+    report.accepted = accepted.len();
+    Ok(report)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("store error")]
+    Store(#[from] store::Error),
+    #[error("invalid bundle")]
+    Bundle(#[from] bundle::Error),
+}
+
+use std::collections::BTreeSet;
+use std::io;
+
+use crate::bundle;
+use crate::note;
+use crate::store;
+use crate::verify;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::controller;
+    use crate::hlc;
+    use fake::Fake as _;
+    use note::Sign as _;
+    use rand::RngCore as _;
+    use rand::SeedableRng as _;
+
+    fn fake_signed_notes(count: usize) -> Vec<note::Signed<note::Note>> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut secret_key_bytes = [0; 32];
+        rng.fill_bytes(&mut secret_key_bytes);
+        let keypair = libp2p::identity::Keypair::ed25519_from_bytes(secret_key_bytes)
+            .expect("Failed to generate keypair");
+
+        (0..count)
+            .map(|_| {
+                let mut note: note::Note = fake::Faker.fake_with_rng(&mut rng);
+                // Notes from the future aren't imported.
+                note.created_at = note.created_at.min(time::OffsetDateTime::now_utc());
+                note.hlc = note
+                    .hlc
+                    .min(hlc::Timestamp::from_wall_clock(note.created_at));
+                note.sign(&keypair).expect("Failed to sign note")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn imports_should_count_accepted_duplicate_and_invalid_notes() {
+        let notes = fake_signed_notes(3);
+        let mut tampered = notes[2].clone();
+        tampered.inner.msg.push_str("TAMPERED");
+        let mut future = notes[2].inner.clone();
+        future.created_at = time::OffsetDateTime::now_utc() + time::Duration::days(1);
+        let future = future
+            .sign(&libp2p::identity::Keypair::generate_ed25519())
+            .expect("Failed to sign note");
+
+        let mut bundle = Vec::new();
+        bundle::write(
+            &mut bundle,
+            [&notes[0], &notes[1], &notes[1], &tampered, &future],
+        )
+        .expect("failed to write bundle");
+        bundle.extend(3u32.to_le_bytes());
+        bundle.extend(b"???");
+        // Cut off in the middle of a note.
+        bundle.extend(100u32.to_le_bytes());
+        bundle.extend(b"...");
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let store = store::Store::open(dir.path()).expect("failed to open store");
+        store
+            .append_notes([&notes[0]])
+            .expect("failed to append note");
+
+        let report = import(
+            &store,
+            bundle.as_slice(),
+            false,
+            controller::DEFAULT_MAX_CLOCK_SKEW,
+        )
+        .expect("failed to import");
+
+        assert_eq!(
+            report,
+            Report {
+                accepted: 1,
+                duplicates: 2,
+                invalid: 3,
+                blocked: 0,
+                future: 1,
+            }
+        );
+        assert_eq!(
+            store.load_notes().expect("failed to load notes"),
+            notes[..2]
+        );
+        assert!(store
+            .load_outbox()
+            .expect("failed to load outbox")
+            .is_empty());
+    }
+
+    #[test]
+    fn republished_imports_should_be_queued_in_the_outbox() {
+        let notes = fake_signed_notes(2);
+        let mut bundle = Vec::new();
+        bundle::write(&mut bundle, &notes).expect("failed to write bundle");
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let store = store::Store::open(dir.path()).expect("failed to open store");
+        import(
+            &store,
+            bundle.as_slice(),
+            true,
+            controller::DEFAULT_MAX_CLOCK_SKEW,
+        )
+        .expect("failed to import");
+
+        let again = import(
+            &store,
+            bundle.as_slice(),
+            true,
+            controller::DEFAULT_MAX_CLOCK_SKEW,
+        )
+        .expect("failed to import");
+
+        assert_eq!(again.duplicates, 2);
+        assert_eq!(store.load_outbox().expect("failed to load outbox"), notes);
+    }
+}
@@ -0,0 +1,331 @@
+//! Which keys were rotated to which, and which were revoked.
+//!
+//! A key rotates once. Should it sign several rotations, the first one we hear of counts, whatever time it says it was
+//! made, so whoever steals a key after its owner moved on can't point it somewhere else. Once a key is revoked,
+//! rotations we hear of after don't count, and the one we knew of only if made before the time it was revoked from,
+//! which is how an owner who lost their key takes back a rotation the thief made.
+//!
+//! An identity can vouch for the keys of its owner's devices, so notes from all of them show up as one author. The
+//! device key signs its side of it, so nobody can claim a key they don't hold. It's one level deep: a device can't
+//! vouch for further devices. A device key belongs to the first identity we hear of vouching for it, and as with
+//! rotations, not at all if we heard of it after the identity was revoked, or it was issued from the time the
+//! identity was revoked on. Revoking an identity revokes its devices along with it, a lost device on its own is
+//! revoked with its own key, e.g. from a revocation made ahead of time.
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Keys {
+    rotations: BTreeMap<note::PubKey, note::Signed<note::Rotation>>,
+    revocations: BTreeMap<note::PubKey, note::Signed<note::Revocation>>,
+    /// By device key.
+    devices: BTreeMap<note::PubKey, note::Signed<note::Device>>,
+}
+
+impl Keys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the certificate changed anything. Its signature must have been checked.
+    pub fn add(&mut self, certificate: note::Certificate) -> bool {
+        match certificate {
+            note::Certificate::Rotation(rotation) => {
+                if rotation.inner.new_key == rotation.pub_key
+                    || self.rotations.contains_key(&rotation.pub_key)
+                    || self.revocations.contains_key(&rotation.pub_key)
+                {
+                    return false;
+                }
+                self.rotations.insert(rotation.pub_key.clone(), rotation);
+                true
+            }
+            note::Certificate::Revocation(revocation) => {
+                match self.revocations.get(&revocation.pub_key) {
+                    Some(known) if known.inner.revoked_at <= revocation.inner.revoked_at => false,
+                    _ => {
+                        self.revocations
+                            .insert(revocation.pub_key.clone(), revocation);
+                        true
+                    }
+                }
+            }
+            note::Certificate::Device(device) => {
+                let device_key = device.inner.device_key();
+                if *device_key == device.pub_key
+                    || self.devices.contains_key(device_key)
+                    || self.revocations.contains_key(&device.pub_key)
+                {
+                    return false;
+                }
+                self.devices.insert(device_key.clone(), device);
+                true
+            }
+        }
+    }
+
+    /// From when on the key can't be trusted, if it or the identity it's a device of was revoked.
+    pub fn revoked_at(&self, pub_key: &note::PubKey) -> Option<time::OffsetDateTime> {
+        let own = self.own_revocation(pub_key);
+        let identity = self.identity(pub_key);
+        if identity == pub_key {
+            return own;
+        }
+        own.into_iter().chain(self.own_revocation(identity)).min()
+    }
+
+    fn own_revocation(&self, pub_key: &note::PubKey) -> Option<time::OffsetDateTime> {
+        self.revocations
+            .get(pub_key)
+            .map(|revocation| revocation.inner.revoked_at)
+    }
+
+    /// The identity the key is a device of, else the key itself.
+    pub fn identity<'a>(&'a self, pub_key: &'a note::PubKey) -> &'a note::PubKey {
+        self.device(pub_key)
+            .map_or(pub_key, |device| &device.pub_key)
+    }
+
+    /// The certificate that makes the key a device of an identity, if it counts.
+    pub fn device(&self, pub_key: &note::PubKey) -> Option<&note::Signed<note::Device>> {
+        let device = self.devices.get(pub_key)?;
+        match self.own_revocation(&device.pub_key) {
+            Some(revoked_at) if revoked_at <= device.inner.issued_at => None,
+            _ => Some(device),
+        }
+    }
+
+    /// The key `pub_key` was rotated to, unless the rotation was made after the key was revoked.
+    pub fn rotated_to(&self, pub_key: &note::PubKey) -> Option<&note::PubKey> {
+        let rotation = self.rotations.get(pub_key)?;
+        match self.revoked_at(pub_key) {
+            Some(revoked_at) if revoked_at <= rotation.inner.rotated_at => None,
+            _ => Some(&rotation.inner.new_key),
+        }
+    }
+
+    /// Whether a key was rotated to `pub_key`, whether or not the rotation counts.
+    pub fn is_rotated_to(&self, pub_key: &note::PubKey) -> bool {
+        self.rotations
+            .values()
+            .any(|rotation| rotation.inner.new_key == *pub_key)
+    }
+
+    /// The key the owner of `pub_key` signs with now, following rotations as far as they go.
+    pub fn current<'a>(&'a self, mut pub_key: &'a note::PubKey) -> &'a note::PubKey {
+        let mut seen = BTreeSet::from([pub_key]);
+        while let Some(next) = self.rotated_to(pub_key) {
+            if !seen.insert(next) {
+                break;
+            }
+            pub_key = next;
+        }
+        pub_key
+    }
+
+    /// Everything worth keeping and passing on.
+    pub fn certificates(&self) -> Vec<note::Certificate> {
+        self.rotations
+            .values()
+            .cloned()
+            .map(note::Certificate::Rotation)
+            .chain(
+                self.revocations
+                    .values()
+                    .cloned()
+                    .map(note::Certificate::Revocation),
+            )
+            .chain(
+                self.devices
+                    .values()
+                    .cloned()
+                    .map(note::Certificate::Device),
+            )
+            .collect()
+    }
+}
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use crate::note;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libp2p::identity;
+    use note::Sign as _;
+    use time::macros::datetime;
+
+    fn rotation(
+        old: &identity::Keypair,
+        new: &identity::Keypair,
+        rotated_at: time::OffsetDateTime,
+    ) -> note::Certificate {
+        note::Certificate::Rotation(
+            note::Rotation {
+                new_key: new.public().into(),
+                rotated_at,
+            }
+            .sign(old)
+            .expect("failed to sign rotation"),
+        )
+    }
+
+    fn revocation(
+        key_pair: &identity::Keypair,
+        revoked_at: time::OffsetDateTime,
+    ) -> note::Certificate {
+        note::Certificate::Revocation(
+            note::Revocation {
+                revoked_at,
+                reason: "Laptop was stolen".to_string(),
+            }
+            .sign(key_pair)
+            .expect("failed to sign revocation"),
+        )
+    }
+
+    #[test]
+    fn rotations_should_be_followed_to_the_newest_key() {
+        let [a, b, c, thief] = std::array::from_fn(|_| identity::Keypair::generate_ed25519());
+        let mut keys = Keys::new();
+
+        assert!(keys.add(rotation(&a, &b, datetime!(2024-05-01 10:00 UTC))));
+        assert!(keys.add(rotation(&b, &c, datetime!(2024-06-01 10:00 UTC))));
+        assert!(!keys.add(rotation(&a, &thief, datetime!(2024-07-01 10:00 UTC))));
+        // Nor does claiming to be earlier help.
+        assert!(!keys.add(rotation(&a, &thief, datetime!(2024-04-01 10:00 UTC))));
+
+        let a_key: note::PubKey = a.public().into();
+        let c_key: note::PubKey = c.public().into();
+        assert_eq!(keys.rotated_to(&a_key), Some(&b.public().into()));
+        assert_eq!(keys.current(&a_key), &c_key);
+
+        // Going round in circles stops before the start.
+        assert!(keys.add(rotation(&c, &a, datetime!(2024-07-01 10:00 UTC))));
+        assert_eq!(keys.current(&a_key), &c_key);
+    }
+
+    #[test]
+    fn rotations_made_after_a_revocation_should_not_count() {
+        let [a, b, thief] = std::array::from_fn(|_| identity::Keypair::generate_ed25519());
+        let a_key: note::PubKey = a.public().into();
+        let mut keys = Keys::new();
+
+        assert!(keys.add(rotation(&a, &thief, datetime!(2024-05-02 10:00 UTC))));
+        assert_eq!(keys.current(&a_key), &thief.public().into());
+
+        assert!(keys.add(revocation(&a, datetime!(2024-05-02 00:00 UTC))));
+        assert!(!keys.add(revocation(&a, datetime!(2024-05-03 00:00 UTC))));
+        assert_eq!(
+            keys.revoked_at(&a_key),
+            Some(datetime!(2024-05-02 00:00 UTC))
+        );
+        assert_eq!(keys.current(&a_key), &a_key);
+
+        // Rotations we hear of after the revocation don't count, however early they say they were made.
+        assert!(!keys.add(rotation(&a, &b, datetime!(2024-05-01 10:00 UTC))));
+        assert_eq!(keys.current(&a_key), &a_key);
+        assert_eq!(keys.certificates().len(), 2);
+
+        // The rotation we knew of before still counts if made before the key was revoked.
+        let mut keys = Keys::new();
+        assert!(keys.add(rotation(&a, &b, datetime!(2024-05-01 10:00 UTC))));
+        assert!(keys.add(revocation(&a, datetime!(2024-05-02 00:00 UTC))));
+        assert_eq!(keys.current(&a_key), &b.public().into());
+    }
+
+    fn membership(
+        identity: &identity::Keypair,
+        device: &identity::Keypair,
+    ) -> note::Signed<note::Membership> {
+        note::Membership {
+            identity: identity.public().into(),
+        }
+        .sign(device)
+        .expect("failed to sign membership")
+    }
+
+    fn device(
+        identity: &identity::Keypair,
+        device: &identity::Keypair,
+        issued_at: time::OffsetDateTime,
+    ) -> note::Certificate {
+        vouch(identity, membership(identity, device), issued_at)
+    }
+
+    fn vouch(
+        identity: &identity::Keypair,
+        membership: note::Signed<note::Membership>,
+        issued_at: time::OffsetDateTime,
+    ) -> note::Certificate {
+        note::Certificate::Device(
+            note::Device {
+                membership: Box::new(membership),
+                name: "laptop".to_string(),
+                issued_at,
+            }
+            .sign(identity)
+            .expect("failed to sign device"),
+        )
+    }
+
+    #[test]
+    fn devices_should_belong_to_the_identity_that_vouched_first() {
+        let [root, laptop, other, thief] =
+            std::array::from_fn(|_| identity::Keypair::generate_ed25519());
+        let root_key: note::PubKey = root.public().into();
+        let laptop_key: note::PubKey = laptop.public().into();
+        let thief_key: note::PubKey = thief.public().into();
+        let mut keys = Keys::new();
+
+        assert!(!keys.add(device(&root, &root, datetime!(2024-05-01 10:00 UTC))));
+        assert!(keys.add(device(&root, &laptop, datetime!(2024-05-01 10:00 UTC))));
+        assert!(!keys.add(device(&other, &laptop, datetime!(2024-05-02 10:00 UTC))));
+        assert_eq!(keys.identity(&laptop_key), &root_key);
+        assert_eq!(keys.identity(&root_key), &root_key);
+
+        // Revoking the identity revokes its devices, and it can't vouch for any more, whenever it says it did.
+        assert!(keys.add(revocation(&root, datetime!(2024-06-01 00:00 UTC))));
+        assert!(!keys.add(device(&root, &thief, datetime!(2024-05-02 10:00 UTC))));
+        assert_eq!(
+            keys.revoked_at(&laptop_key),
+            Some(datetime!(2024-06-01 00:00 UTC))
+        );
+        assert_eq!(keys.identity(&thief_key), &thief_key);
+        assert_eq!(keys.revoked_at(&thief_key), None);
+    }
+
+    #[test]
+    fn keys_should_not_be_claimed_without_their_holder() {
+        let [root, laptop, mallory] =
+            std::array::from_fn(|_| identity::Keypair::generate_ed25519());
+        let laptop_key: note::PubKey = laptop.public().into();
+        let issued_at = datetime!(2024-05-01 10:00 UTC);
+
+        // Mallory can't make the laptop agree, nor pass off its agreement with someone else as one with her.
+        let forged = vouch(&mallory, membership(&mallory, &mallory), issued_at);
+        let note::Certificate::Device(mut swapped) = forged.clone() else {
+            unreachable!("vouching makes device certificates");
+        };
+        swapped.inner.membership = Box::new(membership(&root, &laptop));
+        let swapped =
+            note::Certificate::Device(swapped.inner.sign(&mallory).expect("failed to sign device"));
+        let borrowed = vouch(&mallory, membership(&root, &laptop), issued_at);
+        let mut spoofed = membership(&mallory, &mallory);
+        spoofed.pub_key = laptop_key.clone();
+        let spoofed = vouch(&mallory, spoofed, issued_at);
+        assert!(forged.verify());
+        for certificate in [swapped, borrowed, spoofed] {
+            assert!(!certificate.verify());
+        }
+
+        // Nor does claiming to have been first take the laptop from its identity, even with its agreement.
+        let mut keys = Keys::new();
+        assert!(keys.add(device(&root, &laptop, issued_at)));
+        let backdated = device(&mallory, &laptop, datetime!(2024-01-01 10:00 UTC));
+        assert!(backdated.verify());
+        assert!(!keys.add(backdated));
+        assert_eq!(keys.identity(&laptop_key), &root.public().into());
+    }
+}
@@ -1,6 +1,23 @@
 pub mod app;
+pub mod attachment;
+pub mod backup;
+pub mod bundle;
+pub mod canonical;
+pub mod cli;
 pub mod components;
 pub mod controller;
+pub mod export;
+pub mod hlc;
+pub mod import;
+pub mod keys;
 pub mod model;
 pub mod note;
+pub mod notify;
+pub mod parts;
+pub mod rate_limit;
+pub mod relay;
+pub mod search;
+pub mod ssh;
+pub mod store;
 pub mod tui;
+pub mod verify;
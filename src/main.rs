@@ -1,12 +1,305 @@
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let mut terminal = n2p::tui::init_terminal()?;
+fn main() -> anyhow::Result<()> {
+    // Only works while we are the only thread, so before the runtime starts.
+    let local_offset = time::UtcOffset::current_local_offset().unwrap_or(time::UtcOffset::UTC);
 
-    let mut app = n2p::app::App::new()?;
+    tokio::runtime::Runtime::new()?.block_on(run(local_offset))
+}
+
+async fn run(local_offset: time::UtcOffset) -> anyhow::Result<()> {
+    let cli = n2p::cli::Cli::parse();
+
+    match cli.command {
+        Some(n2p::cli::Command::Relay {
+            ref listen_addrs,
+            ref external_addrs,
+        }) => {
+            let swarm_key = cli.swarm_key()?;
+            let mut listen_addrs = listen_addrs.clone();
+            if swarm_key.is_some() {
+                listen_addrs.retain(|addr| !n2p::cli::is_quic(addr));
+            }
+
+            let key = cli
+                .store()?
+                .load_or_create_relay_identity()
+                .context("failed to load relay identity")?;
+            let mut relay = n2p::relay::Relay::new(n2p::relay::Config {
+                listen_addrs,
+                external_addrs: external_addrs.clone(),
+                swarm_key,
+                key,
+            })?;
+
+            println!("Running relay as {}", relay.local_peer_id());
+            relay.run().await?;
+        }
+
+        Some(n2p::cli::Command::Export {
+            ref topic,
+            format,
+            ref output,
+        }) => {
+            let model = cli.store()?.load_model()?;
+            let writer: Box<dyn std::io::Write> = match output {
+                Some(path) => Box::new(std::io::BufWriter::new(
+                    std::fs::File::create(path)
+                        .with_context(|| format!("failed to create {}", path.display()))?,
+                )),
+                None => Box::new(std::io::stdout().lock()),
+            };
+
+            n2p::export::export(&model, topic.as_deref(), format, local_offset, writer)?;
+        }
+
+        Some(n2p::cli::Command::Import {
+            ref bundle,
+            republish,
+        }) => {
+            let reader = std::io::BufReader::new(
+                std::fs::File::open(bundle)
+                    .with_context(|| format!("failed to open {}", bundle.display()))?,
+            );
+            let report = n2p::import::import(
+                &cli.store()?,
+                reader,
+                republish,
+                n2p::controller::DEFAULT_MAX_CLOCK_SKEW,
+            )?;
+
+            println!("Imported {report}.");
+        }
+
+        Some(n2p::cli::Command::Profile {
+            ref display_name,
+            ref status,
+            ref avatar,
+        }) => {
+            let avatar_hash = match avatar {
+                Some(path) => Some(
+                    sha3::Keccak256::digest(std::fs::read(path).with_context(|| {
+                        format!("failed to read avatar from {}", path.display())
+                    })?)
+                    .into(),
+                ),
+                None => None,
+            };
+
+            let store = cli.store()?;
+            let signing_key = cli.signing_key()?;
+            let profile = n2p::note::Profile {
+                display_name: display_name.clone(),
+                status: status.clone(),
+                avatar_hash,
+                updated_at: time::OffsetDateTime::now_utc(),
+            }
+            .sign(&signing_key)?;
+
+            let mut profiles = store.load_profiles()?;
+            profiles.insert(profile.pub_key.clone(), profile);
+            store.save_profiles(&profiles)?;
+
+            println!("Profile saved. Peers will see it next time you're online.");
+        }
+
+        Some(n2p::cli::Command::Whoami) => {
+            let pub_key = n2p::note::PubKey::from(cli.signing_key()?.public());
+            println!("Key: {pub_key}");
+            if let Some(fingerprint) = pub_key.ssh_fingerprint() {
+                println!("Fingerprint: {fingerprint}");
+            }
+
+            let mut keys = n2p::keys::Keys::new();
+            for certificate in cli.store()?.load_certificates()? {
+                keys.add(certificate);
+            }
+            if let Some(device) = keys.device(&pub_key) {
+                println!("Device \"{}\" of: {}", device.inner.name, device.pub_key);
+            }
+        }
+
+        Some(n2p::cli::Command::Rotate) => {
+            let store = cli.store()?;
+            let old_key = cli.signing_key()?;
+            let new_key = libp2p::identity::Keypair::generate_ed25519();
+            let rotation = n2p::note::Rotation {
+                new_key: new_key.public().into(),
+                rotated_at: time::OffsetDateTime::now_utc(),
+            }
+            .sign(&old_key)?;
+            // Should saving the key fail, there mustn't be a rotation to a key nobody holds: a key rotates once.
+            store.save_identity(&new_key)?;
+
+            // Our profile moves along, peers don't know the new key by name yet.
+            let mut profiles = store.load_profiles()?;
+            if let Some(profile) = profiles.get(&old_key.public().into()) {
+                let profile = n2p::note::Profile {
+                    updated_at: rotation.inner.rotated_at,
+                    ..profile.inner.clone()
+                }
+                .sign(&new_key)?;
+                profiles.insert(profile.pub_key.clone(), profile);
+                store.save_profiles(&profiles)?;
+            }
+
+            store.add_certificate(n2p::note::Certificate::Rotation(rotation))?;
+
+            println!(
+                "Now signing as {}.",
+                n2p::note::PubKey::from(new_key.public())
+            );
+            println!("Peers will follow the rotation next time you're online.");
+        }
 
-    app.run(&mut terminal).await?;
+        Some(n2p::cli::Command::Revoke {
+            since,
+            ref reason,
+            ref output,
+        }) => {
+            let revocation = n2p::note::Certificate::Revocation(
+                n2p::note::Revocation {
+                    revoked_at: since.unwrap_or_else(time::OffsetDateTime::now_utc),
+                    reason: reason.clone(),
+                }
+                .sign(&cli.signing_key()?)?,
+            );
 
-    n2p::tui::restore_terminal()?;
+            match output {
+                Some(path) => {
+                    std::fs::write(path, revocation.encode_to_vec()?)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    println!(
+                        "Revocation written. Publish it with `n2p publish` if the key is lost."
+                    );
+                }
+                None => {
+                    cli.store()?.add_certificate(revocation)?;
+                    println!("Key revoked. Peers will see it next time you're online.");
+                }
+            }
+        }
+
+        Some(n2p::cli::Command::Join {
+            ref identity,
+            ref output,
+        }) => {
+            let membership = n2p::note::Membership {
+                identity: identity.clone(),
+            }
+            .sign(&cli.signing_key()?)?;
+
+            std::fs::write(output, membership.encode_to_vec()?)
+                .with_context(|| format!("failed to write {}", output.display()))?;
+            println!(
+                "Agreement written. Vouch for this device with `n2p device` where {identity} signs."
+            );
+        }
+
+        Some(n2p::cli::Command::Device {
+            ref membership,
+            ref name,
+            ref output,
+        }) => {
+            let data = std::fs::read(membership)
+                .with_context(|| format!("failed to read {}", membership.display()))?;
+            let membership = n2p::note::Signed::<n2p::note::Membership>::decode(data.as_slice())
+                .context("invalid agreement")?;
+            let signing_key = cli.signing_key()?;
+            anyhow::ensure!(membership.verify(), "the agreement's signature is invalid");
+            anyhow::ensure!(
+                membership.inner.identity == signing_key.public().into(),
+                "the agreement is with {}, not with the key we sign with",
+                membership.inner.identity
+            );
+
+            let device = n2p::note::Certificate::Device(
+                n2p::note::Device {
+                    membership: Box::new(membership),
+                    name: name.clone(),
+                    issued_at: time::OffsetDateTime::now_utc(),
+                }
+                .sign(&signing_key)?,
+            );
+
+            match output {
+                Some(path) => {
+                    std::fs::write(path, device.encode_to_vec()?)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    println!("Certificate written. Publish it on the device with `n2p publish`.");
+                }
+                None => {
+                    cli.store()?.add_certificate(device)?;
+                    println!("Device added. Peers will see it next time you're online.");
+                }
+            }
+        }
+
+        Some(n2p::cli::Command::Publish { ref certificate }) => {
+            let data = std::fs::read(certificate)
+                .with_context(|| format!("failed to read {}", certificate.display()))?;
+            let certificate =
+                n2p::note::Certificate::decode(data.as_slice()).context("invalid certificate")?;
+            anyhow::ensure!(
+                certificate.verify(),
+                "the certificate's signature is invalid"
+            );
+            cli.store()?.add_certificate(certificate)?;
+
+            println!("Certificate saved. Peers will see it next time you're online.");
+        }
+
+        Some(n2p::cli::Command::Backup) => {
+            let n2p::note::SigningKey::KeyPair(key_pair) = cli.signing_key()? else {
+                anyhow::bail!("keys in the SSH agent can't be backed up as words");
+            };
+
+            println!("{}", n2p::backup::to_words(&key_pair)?);
+            eprintln!(
+                "Anyone with these words can sign as you. Write them down and keep them offline."
+            );
+        }
+
+        Some(n2p::cli::Command::Restore { force }) => {
+            // Piped in when restoring from a script.
+            let words = if std::io::stdin().is_terminal() {
+                rpassword::prompt_password("Words: ")?
+            } else {
+                std::io::read_to_string(std::io::stdin())?
+            };
+            let key_pair = n2p::backup::from_words(&words)?;
+
+            let store = cli.store()?;
+            if let Some(current) = store.load_identity()? {
+                anyhow::ensure!(
+                    force || current.public() == key_pair.public(),
+                    "there is an identity already, pass --force to replace it"
+                );
+            }
+            store.save_identity(&key_pair)?;
+
+            println!("Restored {}.", n2p::note::PubKey::from(key_pair.public()));
+        }
+
+        None => {
+            let config = cli.controller_config()?;
+
+            let mut terminal = n2p::tui::init_terminal()?;
+
+            let mut app = n2p::app::App::new(config, local_offset)?;
+
+            app.run(&mut terminal).await?;
+
+            n2p::tui::restore_terminal()?;
+        }
+    }
 
     Ok(())
 }
+
+use anyhow::Context as _;
+use clap::Parser as _;
+use n2p::note::Decode as _;
+use n2p::note::Encode as _;
+use n2p::note::Sign as _;
+use n2p::note::Signer as _;
+use sha3::Digest as _;
+use std::io::IsTerminal as _;
@@ -1,17 +1,368 @@
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Model {
     pub topics: BTreeMap<String, Topic>,
+    pub peers: BTreeMap<libp2p::PeerId, Peer>,
+    /// Our own notes that no peer has received yet.
+    pub outbox: BTreeMap<note::NoteId, note::Signed<note::Note>>,
+    /// Our own notes that have been published.
+    pub delivered: BTreeSet<note::NoteId>,
+    /// Who has confirmed receiving a note, by identity.
+    pub receipts: BTreeMap<note::NoteId, BTreeSet<note::PubKey>>,
+    /// Our own names and remarks for authors.
+    pub contacts: BTreeMap<note::PubKey, Contact>,
+    /// What authors say about themselves.
+    pub profiles: BTreeMap<note::PubKey, note::Signed<note::Profile>>,
+    /// Rotations and revocations of authors' keys.
+    pub keys: keys::Keys,
+    pub blocks: Blocks,
+    /// The notes we've read in each topic. The others in [`Topic::notes`] are unread, however old they say they are.
+    pub read: BTreeMap<String, BTreeSet<note::NoteId>>,
+    /// Our own key, so our notes don't count as unread and we notice when we're mentioned.
+    pub me: Option<note::PubKey>,
+    /// Attachments we fetch or saved, by root.
+    pub transfers: BTreeMap<attachment::Hash, Transfer>,
+    /// Notes that came in parts, until all parts are there.
+    pub parts: parts::Reassembly,
+    pub index: search::Index,
+    pub status: Option<String>,
 }
 
 impl Model {
     pub fn new() -> Self {
         Self::default()
     }
+
+    pub fn add_note(&mut self, note: note::Signed<note::Note>) {
+        self.index.add_note(&note);
+        self.topics
+            .entry(note.inner.topic.clone())
+            .or_default()
+            .add_note(note);
+    }
+
+    pub fn contains(&self, id: &note::NoteId) -> bool {
+        self.index.location(id).is_some()
+    }
+
+    pub fn remove_notes_by(&mut self, pub_key: &note::PubKey) {
+        for topic in self.topics.values_mut() {
+            topic.notes.retain(|_, note| &note.pub_key != pub_key);
+        }
+        self.index.remove_author(pub_key);
+    }
+
+    /// Newest first. Muted authors are left out, like in the topic itself.
+    pub fn search(&self, query: &search::Query) -> Vec<&note::Signed<note::Note>> {
+        let mut ids = self.index.all();
+        for term in &query.terms {
+            let mut matching = self.index.matching(term);
+            for pub_key in self.index.authors() {
+                if self
+                    .author_name(pub_key)
+                    .to_lowercase()
+                    .contains(term.as_str())
+                {
+                    matching.extend(self.index.by_author(pub_key));
+                }
+            }
+            ids.retain(|id| matching.contains(id));
+        }
+
+        let mut notes: Vec<_> = ids
+            .iter()
+            .filter_map(|id| self.index.location(id))
+            .filter(|location| {
+                query
+                    .topic
+                    .as_ref()
+                    .is_none_or(|topic| location.topic.to_lowercase() == *topic)
+            })
+            .filter_map(|location| self.topics.get(&location.topic)?.notes.get(&location.key))
+            .filter(|note| {
+                query.from.as_ref().is_none_or(|from| {
+                    self.author_name(&note.pub_key)
+                        .to_lowercase()
+                        .contains(from.as_str())
+                        || note.pub_key.to_string().starts_with(from.as_str())
+                })
+            })
+            .filter(|note| {
+                query
+                    .before
+                    .is_none_or(|before| note.inner.created_at < before)
+            })
+            .filter(|note| {
+                query
+                    .after
+                    .is_none_or(|after| note.inner.created_at >= after)
+            })
+            .filter(|note| !self.is_muted(&note.inner.topic, &note.pub_key))
+            .collect();
+
+        notes.sort_by_key(|note| std::cmp::Reverse((note.inner.hlc, note.id())));
+        notes
+    }
+
+    /// Whether a note we have carries the attachment with `root`.
+    pub fn has_attachment(&self, root: &attachment::Hash) -> bool {
+        self.topics
+            .values()
+            .flat_map(|topic| topic.notes.values())
+            .flat_map(|note| &note.inner.attachments)
+            .any(|attachment| attachment.root == *root)
+    }
+
+    pub fn seen_by(&self, id: &note::NoteId) -> usize {
+        self.receipts.get(id).map_or(0, BTreeSet::len)
+    }
+
+    /// Keeps only the newest profile of each author. Returns whether the profile was newer.
+    pub fn add_profile(&mut self, profile: note::Signed<note::Profile>) -> bool {
+        match self.profiles.get(&profile.pub_key) {
+            Some(known) if known.inner.updated_at >= profile.inner.updated_at => false,
+            _ => {
+                self.profiles.insert(profile.pub_key.clone(), profile);
+                true
+            }
+        }
+    }
+
+    /// Returns whether the certificate was new. Contacts follow rotations, so the new key gets the petname and trust
+    /// we gave the old one, unless we have a contact for it already.
+    pub fn add_certificate(&mut self, certificate: note::Certificate) -> bool {
+        if !self.keys.add(certificate) {
+            return false;
+        }
+
+        let moved: Vec<_> = self
+            .contacts
+            .iter()
+            .filter_map(|(pub_key, contact)| {
+                let current = self.keys.current(pub_key);
+                (!self.contacts.contains_key(current)).then(|| (current.clone(), contact.clone()))
+            })
+            .collect();
+        self.contacts.extend(moved);
+
+        true
+    }
+
+    /// Whether the note was signed with a key its owner revoked by then. A thief can backdate notes, so notes before
+    /// the revocation aren't certain to be from the owner either.
+    pub fn is_revoked(&self, note: &note::Signed<note::Note>) -> bool {
+        self.keys
+            .revoked_at(&note.pub_key)
+            .is_some_and(|revoked_at| revoked_at <= note.inner.created_at)
+    }
+
+    /// The petname we gave the author, else the name they chose marked with `~`, else a short form of their key.
+    /// Devices go by the name of their identity.
+    pub fn author_name(&self, pub_key: &note::PubKey) -> String {
+        let pub_key = self.keys.identity(pub_key);
+        if let Some(contact) = self.contacts.get(pub_key) {
+            if !contact.petname.is_empty() {
+                return contact.petname.clone();
+            }
+        }
+
+        match self.profile(pub_key) {
+            Some(profile) if !profile.inner.display_name.is_empty() => {
+                format!("~{}", profile.inner.display_name)
+            }
+            _ => pub_key.short(),
+        }
+    }
+
+    /// The profile of the identity the key is a device of, else the key's own.
+    pub fn profile(&self, pub_key: &note::PubKey) -> Option<&note::Signed<note::Profile>> {
+        self.profiles
+            .get(self.keys.identity(pub_key))
+            .or_else(|| self.profiles.get(pub_key))
+    }
+
+    /// Whether the key is ours, or another device of our identity.
+    pub fn is_mine(&self, pub_key: &note::PubKey) -> bool {
+        self.me
+            .as_ref()
+            .is_some_and(|me| self.keys.identity(me) == self.keys.identity(pub_key))
+    }
+
+    /// Whether the key is ours, or we have notes or a profile by it, or a contact for it, or a key was rotated to it.
+    pub fn knows(&self, pub_key: &note::PubKey) -> bool {
+        self.is_mine(pub_key)
+            || self.index.has_author(pub_key)
+            || self.profiles.contains_key(pub_key)
+            || self.contacts.contains_key(pub_key)
+            || self.keys.is_rotated_to(pub_key)
+    }
+
+    /// Whether we blocked the author, or the identity they are a device of.
+    pub fn is_blocked(&self, pub_key: &note::PubKey) -> bool {
+        self.blocks.authors.contains(pub_key)
+            || self.blocks.authors.contains(self.keys.identity(pub_key))
+    }
+
+    /// Whether we muted the author in the topic, or the identity they are a device of.
+    pub fn is_muted(&self, topic: &str, pub_key: &note::PubKey) -> bool {
+        self.blocks.is_muted(topic, pub_key)
+            || self.blocks.is_muted(topic, self.keys.identity(pub_key))
+    }
+
+    /// Whether the note calls us by our identity's key or by the name in our profile.
+    pub fn mentions_me(&self, note: &note::Note) -> bool {
+        let Some(me) = &self.me else {
+            return false;
+        };
+        let name = self
+            .profile(me)
+            .map_or("", |profile| profile.inner.display_name.as_str());
+
+        notify::mentions(&note.msg, name, me)
+            || notify::mentions(&note.msg, name, self.keys.identity(me))
+    }
+
+    /// Notes by others we haven't read, leaving out muted authors.
+    pub fn unread(&self, topic: &str) -> Unread {
+        let Some(notes) = self.topics.get(topic).map(|topic| &topic.notes) else {
+            return Unread::default();
+        };
+        let read = self.read.get(topic);
+
+        notes
+            .iter()
+            .filter(|((_, id), _)| !read.is_some_and(|read| read.contains(id)))
+            .map(|(_, note)| note)
+            .filter(|note| !self.is_mine(&note.pub_key))
+            .filter(|note| !self.is_muted(topic, &note.pub_key))
+            .fold(Unread::default(), |unread, note| Unread {
+                notes: unread.notes + 1,
+                mentions: unread.mentions + usize::from(self.mentions_me(&note.inner)),
+            })
+    }
+
+    /// Marks every note in the topic read. Returns whether any wasn't.
+    pub fn mark_read(&mut self, topic: &str) -> bool {
+        let Some(notes) = self.topics.get(topic).map(|topic| &topic.notes) else {
+            return false;
+        };
+
+        let read = self.read.entry(topic.to_string()).or_default();
+        let before = read.len();
+        read.extend(notes.keys().map(|(_, id)| *id));
+        read.len() > before
+    }
+
+    pub fn delivery(&self, id: &note::NoteId) -> Option<Delivery> {
+        if self.outbox.contains_key(id) {
+            Some(Delivery::Pending)
+        } else if self.delivered.contains(id) {
+            Some(Delivery::Delivered)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Unread {
+    pub notes: usize,
+    /// Unread notes that mention us.
+    pub mentions: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transfer {
+    /// `total` is 0 until a peer sent us the chunk list.
+    Fetching {
+        received: usize,
+        total: usize,
+    },
+    Saved(std::path::PathBuf),
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Pending,
+    Delivered,
+}
+
+/// Who we don't want to hear from.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Blocks {
+    /// Notes by these authors are dropped on arrival.
+    pub authors: BTreeSet<note::PubKey>,
+    /// Connections to and from these peers are refused.
+    pub peers: BTreeSet<libp2p::PeerId>,
+    /// Authors hidden per topic. Their notes are still kept.
+    pub mutes: BTreeMap<String, BTreeSet<note::PubKey>>,
+}
+
+impl Blocks {
+    pub fn is_muted(&self, topic: &str, pub_key: &note::PubKey) -> bool {
+        self.mutes
+            .get(topic)
+            .is_some_and(|muted| muted.contains(pub_key))
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Contact {
+    pub petname: String,
+    pub notes: String,
+    pub trust: Trust,
+}
+
+/// How sure we are that a key belongs to who we think it does.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum Trust {
+    #[default]
+    Unknown,
+    /// We know them, but haven't checked the key.
+    Known,
+    /// The key was checked out of band.
+    Verified,
+}
+
+impl Trust {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Unknown => Self::Known,
+            Self::Known => Self::Verified,
+            Self::Verified => Self::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for Trust {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "unknown"),
+            Self::Known => write!(f, "known"),
+            Self::Verified => write!(f, "verified"),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Topic {
-    pub notes: BTreeMap<time::PrimitiveDateTime, note::Signed<note::Note>>,
+    /// By clock, then ID. Use [`Topic::ordered`] for the order to show them in.
+    pub notes: BTreeMap<(hlc::Timestamp, note::NoteId), note::Signed<note::Note>>,
+    /// Notes nobody has listed as parent yet. New notes list these.
+    pub heads: BTreeSet<note::NoteId>,
+    /// Every note listed as parent, whether we have it or not.
+    parents: BTreeSet<note::NoteId>,
 }
 
 impl Topic {
@@ -20,10 +371,438 @@ impl Topic {
     }
 
     pub fn add_note(&mut self, note: note::Signed<note::Note>) {
-        self.notes.insert(note.inner.created_at, note);
+        let id = note.id();
+        if !self.parents.contains(&id) {
+            self.heads.insert(id);
+        }
+        for parent in &note.inner.parents {
+            self.heads.remove(parent);
+            self.parents.insert(*parent);
+        }
+
+        self.notes.insert((note.inner.hlc, id), note);
+    }
+
+    /// Parents come before their children, even when a clock says otherwise. Anything else goes by clock, then ID.
+    /// Only depends on which notes we have, so everyone with the same notes sees the same order.
+    pub fn ordered(&self) -> Vec<&note::Signed<note::Note>> {
+        let keys: BTreeMap<_, _> = self.notes.keys().map(|key| (key.1, *key)).collect();
+
+        let mut waiting_for = BTreeMap::new();
+        let mut children: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (key, note) in &self.notes {
+            let parents: BTreeSet<_> = note
+                .inner
+                .parents
+                .iter()
+                .filter_map(|parent| keys.get(parent))
+                .collect();
+            for parent in &parents {
+                children.entry(**parent).or_default().push(*key);
+            }
+            waiting_for.insert(*key, parents.len());
+        }
+
+        let mut ready: BTreeSet<_> = waiting_for
+            .iter()
+            .filter(|(_, waiting_for)| **waiting_for == 0)
+            .map(|(key, _)| *key)
+            .collect();
+        let mut ordered = Vec::with_capacity(self.notes.len());
+        while let Some(key) = ready.pop_first() {
+            ordered.push(&self.notes[&key]);
+            for child in children.get(&key).into_iter().flatten() {
+                let waiting_for = waiting_for.get_mut(child).expect("child is a note");
+                *waiting_for -= 1;
+                if *waiting_for == 0 {
+                    ready.insert(*child);
+                }
+            }
+        }
+
+        ordered
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Peer {
+    pub addresses: BTreeSet<libp2p::Multiaddr>,
+    pub transport: Option<Transport>,
+    pub connected_since: Option<time::OffsetDateTime>,
+    pub topics: BTreeSet<String>,
+    pub agent_version: Option<String>,
+    pub rtt: Option<std::time::Duration>,
+}
+
+impl Peer {
+    pub fn is_connected(&self) -> bool {
+        self.connected_since.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Transport {
+    Tcp,
+    Quic,
+    Relayed,
+    Other,
+}
+
+impl Transport {
+    pub fn of(addr: &libp2p::Multiaddr) -> Self {
+        let mut transport = Self::Other;
+        for protocol in addr.iter() {
+            match protocol {
+                libp2p::multiaddr::Protocol::P2pCircuit => return Self::Relayed,
+                libp2p::multiaddr::Protocol::QuicV1 => transport = Self::Quic,
+                libp2p::multiaddr::Protocol::Tcp(_) => transport = Self::Tcp,
+                _ => (),
+            }
+        }
+        transport
+    }
+}
+
+impl std::fmt::Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "TCP"),
+            Self::Quic => write!(f, "QUIC"),
+            Self::Relayed => write!(f, "relayed"),
+            Self::Other => write!(f, "other"),
+        }
     }
 }
 
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 
+use crate::attachment;
+use crate::hlc;
+use crate::keys;
 use crate::note;
+use crate::notify;
+use crate::parts;
+use crate::search;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use note::Sign as _;
+    use rand::seq::SliceRandom as _;
+    use rand::SeedableRng as _;
+
+    #[test]
+    fn older_profiles_should_not_replace_newer_ones() {
+        let key_pair = libp2p::identity::Keypair::generate_ed25519();
+        let profile = |display_name: &str, updated_at| {
+            note::Profile {
+                display_name: display_name.to_string(),
+                status: String::new(),
+                avatar_hash: None,
+                updated_at,
+            }
+            .sign(&key_pair)
+            .expect("failed to sign profile")
+        };
+        let now = time::OffsetDateTime::now_utc();
+        let mut model = Model::new();
+
+        assert!(model.add_profile(profile("new", now)));
+        assert!(!model.add_profile(profile("old", now - time::Duration::hours(1))));
+
+        assert_eq!(
+            model.author_name(&key_pair.public().into()),
+            "~new".to_string()
+        );
+    }
+
+    #[test]
+    fn petnames_should_come_before_the_names_authors_chose() {
+        let key_pair = libp2p::identity::Keypair::generate_ed25519();
+        let pub_key: note::PubKey = key_pair.public().into();
+        let profile = note::Profile {
+            display_name: "alice".to_string(),
+            status: String::new(),
+            avatar_hash: None,
+            updated_at: time::OffsetDateTime::now_utc(),
+        }
+        .sign(&key_pair)
+        .expect("failed to sign profile");
+        let mut model = Model::new();
+
+        assert_eq!(model.author_name(&pub_key), pub_key.short());
+        assert!(model.add_profile(profile));
+        assert_eq!(model.author_name(&pub_key), "~alice");
+
+        // A contact without a petname doesn't hide the name they chose.
+        model.contacts.insert(pub_key.clone(), Contact::default());
+        assert_eq!(model.author_name(&pub_key), "~alice");
+
+        model.contacts.insert(
+            pub_key.clone(),
+            Contact {
+                petname: "Alice from work".to_string(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(model.author_name(&pub_key), "Alice from work");
+    }
+
+    #[test]
+    fn unread_notes_should_count_from_the_read_marker() {
+        let me = libp2p::identity::Keypair::generate_ed25519();
+        let bob = libp2p::identity::Keypair::generate_ed25519();
+        let now = time::OffsetDateTime::now_utc();
+        let mut clock = hlc::Clock::new();
+        let mut note = |key_pair: &libp2p::identity::Keypair, msg: &str| {
+            note::Note {
+                hlc: clock.tick(now),
+                ..note::tests::note_with("ops", msg, now)
+            }
+            .sign(key_pair)
+            .expect("failed to sign note")
+        };
+
+        let mut model = Model::new();
+        model.me = Some(me.public().into());
+        model.add_note(note(&bob, "Morning"));
+        model.add_note(note(&me, "Morning Bob"));
+
+        assert_eq!(
+            model.unread("ops"),
+            Unread {
+                notes: 1,
+                mentions: 0
+            }
+        );
+        assert!(model.mark_read("ops"));
+        assert!(!model.mark_read("ops"));
+        assert_eq!(model.unread("ops"), Unread::default());
+
+        let me_key: note::PubKey = me.public().into();
+        model.add_note(note(&bob, "Anyone on call?"));
+        model.add_note(note(&bob, &format!("{}, are you?", me_key.short())));
+
+        assert_eq!(
+            model.unread("ops"),
+            Unread {
+                notes: 2,
+                mentions: 1
+            }
+        );
+        assert_eq!(model.unread("random"), Unread::default());
+    }
+
+    #[test]
+    fn late_notes_should_be_unread_however_old_they_are() {
+        let bob = libp2p::identity::Keypair::generate_ed25519();
+        let now = time::OffsetDateTime::now_utc();
+        let note = |msg: &str, created_at| {
+            note::tests::note_with("ops", msg, created_at)
+                .sign(&bob)
+                .expect("failed to sign note")
+        };
+
+        let mut model = Model::new();
+        model.add_note(note("Back online", now));
+        assert!(model.mark_read("ops"));
+
+        // Written while Bob was offline, and only sent now.
+        model.add_note(note("Written on the train", now - time::Duration::hours(2)));
+
+        assert_eq!(model.unread("ops").notes, 1);
+        assert!(model.mark_read("ops"));
+        assert_eq!(model.unread("ops"), Unread::default());
+    }
+
+    #[test]
+    fn keys_should_be_revoked_and_followed_by_contacts() {
+        let old = libp2p::identity::Keypair::generate_ed25519();
+        let new = libp2p::identity::Keypair::generate_ed25519();
+        let old_key: note::PubKey = old.public().into();
+        let new_key: note::PubKey = new.public().into();
+        let revoked_at = time::OffsetDateTime::now_utc();
+        let note = |created_at| {
+            note::Note {
+                hlc: hlc::Timestamp::default(),
+                ..note::tests::note_with("ops", "Deploying", created_at)
+            }
+            .sign(&old)
+            .expect("failed to sign note")
+        };
+
+        let mut model = Model::new();
+        model.contacts.insert(
+            old_key.clone(),
+            Contact {
+                petname: "Bob".to_string(),
+                notes: String::new(),
+                trust: Trust::Verified,
+            },
+        );
+
+        let rotation = note::Rotation {
+            new_key: new_key.clone(),
+            rotated_at: revoked_at - time::Duration::days(1),
+        }
+        .sign(&old)
+        .expect("failed to sign rotation");
+        assert!(model.add_certificate(note::Certificate::Rotation(rotation.clone())));
+        assert!(!model.add_certificate(note::Certificate::Rotation(rotation)));
+        assert_eq!(model.contacts[&new_key], model.contacts[&old_key]);
+        assert_eq!(model.author_name(&new_key), "Bob");
+
+        let revocation = note::Revocation {
+            revoked_at,
+            reason: "Laptop was stolen".to_string(),
+        }
+        .sign(&old)
+        .expect("failed to sign revocation");
+        assert!(model.add_certificate(note::Certificate::Revocation(revocation)));
+        assert!(!model.is_revoked(&note(revoked_at - time::Duration::hours(1))));
+        assert!(model.is_revoked(&note(revoked_at)));
+        assert!(model.is_revoked(&note(revoked_at + time::Duration::hours(1))));
+    }
+
+    #[test]
+    fn devices_should_count_as_their_identity() {
+        let desktop = libp2p::identity::Keypair::generate_ed25519();
+        let laptop = libp2p::identity::Keypair::generate_ed25519();
+        let bob = libp2p::identity::Keypair::generate_ed25519();
+        let bob_phone = libp2p::identity::Keypair::generate_ed25519();
+        let now = time::OffsetDateTime::now_utc();
+        let device = |identity: &libp2p::identity::Keypair, device: &libp2p::identity::Keypair| {
+            note::Certificate::Device(
+                note::Device {
+                    membership: Box::new(
+                        note::Membership {
+                            identity: identity.public().into(),
+                        }
+                        .sign(device)
+                        .expect("failed to sign membership"),
+                    ),
+                    name: "phone".to_string(),
+                    issued_at: now,
+                }
+                .sign(identity)
+                .expect("failed to sign device"),
+            )
+        };
+        let note = |key_pair: &libp2p::identity::Keypair, msg: &str| {
+            note::tests::note_with("ops", msg, now)
+                .sign(key_pair)
+                .expect("failed to sign note")
+        };
+
+        let mut model = Model::new();
+        model.me = Some(laptop.public().into());
+        model.contacts.insert(
+            bob.public().into(),
+            Contact {
+                petname: "Bob".to_string(),
+                ..Default::default()
+            },
+        );
+        assert!(model.add_certificate(device(&desktop, &laptop)));
+        assert!(model.add_certificate(device(&bob, &bob_phone)));
+        model.add_note(note(&desktop, "Sent from my desktop"));
+        model.add_note(note(&bob_phone, "Sent from my phone"));
+
+        assert!(model.is_mine(&desktop.public().into()));
+        assert_eq!(model.author_name(&bob_phone.public().into()), "Bob");
+        assert_eq!(model.unread("ops").notes, 1);
+
+        model.blocks.authors.insert(bob.public().into());
+        assert!(model.is_blocked(&bob_phone.public().into()));
+    }
+
+    /// Authors whose clocks are off by up to an hour write notes, sometimes after catching up on everyone else's.
+    fn conversation(steps: &[(usize, bool)], drifts: &[i64]) -> Vec<note::Signed<note::Note>> {
+        let key_pairs: Vec<_> = (0..drifts.len())
+            .map(|i| {
+                libp2p::identity::Keypair::ed25519_from_bytes([i as u8 + 1; 32])
+                    .expect("failed to generate keypair")
+            })
+            .collect();
+        let mut views = vec![Topic::new(); drifts.len()];
+        let mut clocks = vec![hlc::Clock::new(); drifts.len()];
+        let mut notes: Vec<note::Signed<note::Note>> = Vec::new();
+        let start = time::OffsetDateTime::now_utc();
+
+        for (step, &(author, catch_up)) in steps.iter().enumerate() {
+            if catch_up {
+                for note in &notes {
+                    clocks[author].observe(note.inner.hlc);
+                    views[author].add_note(note.clone());
+                }
+            }
+
+            let now = start
+                + time::Duration::seconds(drifts[author])
+                + time::Duration::seconds(step as i64);
+            let note = note::Note {
+                hlc: clocks[author].tick(now),
+                parents: views[author].heads.iter().copied().collect(),
+                ..note::tests::note_with("Derp", format!("note {step}"), now)
+            }
+            .sign(&key_pairs[author])
+            .expect("failed to sign note");
+
+            views[author].add_note(note.clone());
+            notes.push(note);
+        }
+
+        notes
+    }
+
+    fn replica(notes: &[note::Signed<note::Note>], seed: u64) -> Topic {
+        let mut notes = notes.to_vec();
+        notes.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed));
+
+        let mut topic = Topic::new();
+        for note in notes {
+            topic.add_note(note);
+        }
+        topic
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn replicas_should_converge_whatever_order_notes_arrive_in(
+            steps in proptest::collection::vec((0..3usize, proptest::bool::ANY), 1..30),
+            drifts in proptest::collection::vec(-3600..3600i64, 3),
+            seeds in (proptest::num::u64::ANY, proptest::num::u64::ANY),
+        ) {
+            let notes = conversation(&steps, &drifts);
+            let a = replica(&notes, seeds.0);
+            let b = replica(&notes, seeds.1);
+
+            proptest::prop_assert_eq!(a.ordered(), b.ordered());
+            proptest::prop_assert_eq!(a.heads, b.heads);
+        }
+
+        #[test]
+        fn notes_should_come_after_everything_their_author_had_seen(
+            steps in proptest::collection::vec((0..3usize, proptest::bool::ANY), 1..30),
+            drifts in proptest::collection::vec(-3600..3600i64, 3),
+            seed in proptest::num::u64::ANY,
+        ) {
+            let notes = conversation(&steps, &drifts);
+            let ordered: Vec<_> = replica(&notes, seed)
+                .ordered()
+                .into_iter()
+                .map(|note| note.id())
+                .collect();
+
+            for (position, note) in ordered.iter().enumerate() {
+                let note = notes.iter().find(|n| n.id() == *note).expect("note is known");
+                for parent in &note.inner.parents {
+                    let parent_position = ordered.iter().position(|id| id == parent);
+                    proptest::prop_assert!(parent_position < Some(position));
+                }
+            }
+        }
+    }
+}
@@ -5,9 +5,170 @@
 pub struct Note {
     pub topic: String,
     pub msg: String,
-    pub created_at: time::PrimitiveDateTime,
+    /// We write it in UTC, but it's compared as an instant whatever the offset.
+    pub created_at: time::OffsetDateTime,
+    /// Orders notes by what their authors had seen, rather than by their clocks.
+    pub hlc: hlc::Timestamp,
+    /// The newest notes of the topic the author had seen.
+    pub parents: Vec<NoteId>,
+    pub attachments: Vec<Attachment>,
 }
 
+/// A file that comes with a note. Only this manifest is in the note, the content is fetched from peers in chunks.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[cfg_attr(test, derive(fake::Dummy))]
+pub struct Attachment {
+    pub name: String,
+    pub size: u64,
+    pub mime_type: String,
+    /// Hash of the chunk hashes, see [`crate::attachment::root`].
+    pub root: [u8; 32],
+}
+
+impl Note {
+    /// Whether it claims to be written after `latest`, by its wall clock or its logical one.
+    pub fn is_after(&self, latest: time::OffsetDateTime) -> bool {
+        self.created_at > latest || self.hlc > hlc::Timestamp::from_wall_clock(latest)
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[cfg_attr(test, derive(fake::Dummy))]
+pub struct NoteId(pub [u8; 32]);
+
+impl std::fmt::Display for NoteId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Tells the author of a note that we received it.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Receipt {
+    pub note_id: NoteId,
+}
+
+/// One piece of an encoded `Signed<Note>` too large for a single message, signed by the note's author.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Part {
+    pub note_id: NoteId,
+    pub index: u16,
+    pub count: u16,
+    pub data: Vec<u8>,
+}
+
+/// How an author would like to be shown. The newest one wins.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Profile {
+    pub display_name: String,
+    pub status: String,
+    /// Keccak256 of a small avatar image.
+    pub avatar_hash: Option<[u8; 32]>,
+    pub updated_at: time::OffsetDateTime,
+}
+
+/// The old key vouching for the key its owner signs with from now on. Signed by the old key.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Rotation {
+    pub new_key: PubKey,
+    pub rotated_at: time::OffsetDateTime,
+}
+
+/// Withdraws trust in the key that signs it, e.g. when the device holding it was lost. Whatever the key signed from
+/// `revoked_at` on may not be from its owner.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Revocation {
+    pub revoked_at: time::OffsetDateTime,
+    pub reason: String,
+}
+
+/// An identity vouching for the key of one of its owner's devices, so notes signed there count as the identity's.
+/// Signed by the identity.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Device {
+    /// The device key's side of it, so nobody can claim a key that isn't theirs.
+    pub membership: Box<Signed<Membership>>,
+    /// What the owner calls the device, like "laptop".
+    pub name: String,
+    pub issued_at: time::OffsetDateTime,
+}
+
+impl Device {
+    pub fn device_key(&self) -> &PubKey {
+        &self.membership.pub_key
+    }
+}
+
+/// A device key agreeing to count as a device of the identity. Signed by the device key, and made on the device.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Membership {
+    pub identity: PubKey,
+}
+
+/// What a key says about itself or its devices, gossiped and kept like profiles. See [`crate::keys`].
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum Certificate {
+    Rotation(Signed<Rotation>),
+    Revocation(Signed<Revocation>),
+    Device(Signed<Device>),
+}
+
+impl Certificate {
+    /// The key that signed the certificate.
+    pub fn pub_key(&self) -> &PubKey {
+        match self {
+            Self::Rotation(rotation) => &rotation.pub_key,
+            Self::Revocation(revocation) => &revocation.pub_key,
+            Self::Device(device) => &device.pub_key,
+        }
+    }
+
+    pub fn verify(&self) -> bool {
+        match self {
+            Self::Rotation(rotation) => rotation.verify(),
+            Self::Revocation(revocation) => revocation.verify(),
+            Self::Device(device) => {
+                device.verify()
+                    && device.inner.membership.verify()
+                    && device.inner.membership.inner.identity == device.pub_key
+            }
+        }
+    }
+}
+
+impl Signed<Note> {
+    /// Content address of the note, covering both the note and its author.
+    pub fn id(&self) -> NoteId {
+        NoteId(canonical::hash(NOTE_ID_DOMAIN, self))
+    }
+}
+
+/// Hashed for [`Signed::id`], so an ID can't be mistaken for a signed digest.
+const NOTE_ID_DOMAIN: &str = "n2p/note-id/v1";
+
 #[derive(
     Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
 )]
@@ -18,25 +179,44 @@ pub struct Signed<T> {
 }
 
 impl<T: DigestHash> Signed<T> {
+    /// Ed25519 signatures are checked like [`verify::verify_batch`] checks them, other keys by libp2p.
     pub fn verify(&self) -> bool {
-        let Ok(inner_digest_hash) = self.inner.digest_hash() else {
+        let digest = self.inner.digest_hash();
+        match self.pub_key.ed25519_bytes() {
+            Some(key) => verify::verify_ed25519(key, &digest, &self.signature),
+            None => self.pub_key.0.verify(&digest, &self.signature),
+        }
+    }
+
+    /// [`Signed::verify`], and the identity the signature speaks for: the one whose certificate in `keys` vouches for
+    /// the signing key, else the signing key itself.
+    pub fn verify_chain<'a>(&'a self, keys: &'a keys::Keys) -> Option<&'a PubKey> {
+        self.verify().then(|| keys.identity(&self.pub_key))
+    }
+}
+
+impl<T: serde::Serialize> Signed<T> {
+    /// Signatures made before the [canonical](crate::canonical) encoding, over the Keccak256 digest of the bincode
+    /// encoding. Only for notes from our own history and old bundles, never for what comes in from peers.
+    pub fn verify_legacy(&self) -> bool {
+        let Ok(encoded) = self.inner.encode_to_vec() else {
             return false;
         };
 
-        self.pub_key.0.verify(&inner_digest_hash, &self.signature)
+        self.pub_key
+            .0
+            .verify(&sha3::Keccak256::digest(encoded), &self.signature)
     }
 }
 
 pub trait Sign: Sized {
-    type Error;
-    fn sign(self, key_pair: &identity::Keypair) -> Result<Signed<Self>, Self::Error>;
+    fn sign(self, signer: &impl Signer) -> Result<Signed<Self>, SignError>;
 }
 
 impl<T: DigestHash> Sign for T {
-    type Error = SignError<T::Error>;
-    fn sign(self, key_pair: &identity::Keypair) -> Result<Signed<Self>, Self::Error> {
-        let pub_key = PubKey(key_pair.public());
-        let signature = key_pair.sign(&self.digest_hash().map_err(SignError::DigestError)?)?;
+    fn sign(self, signer: &impl Signer) -> Result<Signed<Self>, SignError> {
+        let pub_key = PubKey(signer.public());
+        let signature = signer.sign(&self.digest_hash())?;
 
         Ok(Signed {
             inner: self,
@@ -46,12 +226,235 @@ impl<T: DigestHash> Sign for T {
     }
 }
 
+/// Holds a private key, or knows who does.
+pub trait Signer {
+    fn public(&self) -> identity::PublicKey;
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SignError>;
+}
+
+impl Signer for identity::Keypair {
+    fn public(&self) -> identity::PublicKey {
+        self.public()
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SignError> {
+        Ok(self.sign(message)?)
+    }
+}
+
+impl Signer for ssh::Agent {
+    fn public(&self) -> identity::PublicKey {
+        self.public()
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SignError> {
+        Ok(self.sign(message)?)
+    }
+}
+
+/// What we sign our notes with.
+#[derive(Debug, Clone)]
+pub enum SigningKey {
+    KeyPair(identity::Keypair),
+    /// A key we don't have, but an SSH agent signs with for us.
+    Agent(ssh::Agent),
+}
+
+impl Signer for SigningKey {
+    fn public(&self) -> identity::PublicKey {
+        match self {
+            Self::KeyPair(key_pair) => Signer::public(key_pair),
+            Self::Agent(agent) => Signer::public(agent),
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SignError> {
+        match self {
+            Self::KeyPair(key_pair) => Signer::sign(key_pair, message),
+            Self::Agent(agent) => Signer::sign(agent, message),
+        }
+    }
+}
+
+impl From<identity::Keypair> for SigningKey {
+    fn from(key_pair: identity::Keypair) -> Self {
+        Self::KeyPair(key_pair)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
-pub enum SignError<DigestError> {
+pub enum SignError {
     #[error("signing error")]
-    SigningError(#[from] identity::SigningError),
-    #[error("digest error")]
-    DigestError(DigestError),
+    Key(#[from] identity::SigningError),
+    #[error("SSH agent error")]
+    Agent(#[from] ssh::Error),
+}
+
+impl canonical::Canonical for Note {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self {
+            topic,
+            msg,
+            created_at,
+            hlc,
+            parents,
+            attachments,
+        } = self;
+        topic.write_canonical(out);
+        msg.write_canonical(out);
+        created_at.write_canonical(out);
+        hlc.write_canonical(out);
+        parents.write_canonical(out);
+        attachments.write_canonical(out);
+    }
+}
+
+impl canonical::Signable for Note {
+    const DOMAIN: &'static str = "n2p/note/v1";
+}
+
+impl canonical::Canonical for Attachment {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self {
+            name,
+            size,
+            mime_type,
+            root,
+        } = self;
+        name.write_canonical(out);
+        size.write_canonical(out);
+        mime_type.write_canonical(out);
+        root.write_canonical(out);
+    }
+}
+
+impl canonical::Canonical for NoteId {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        self.0.write_canonical(out);
+    }
+}
+
+impl canonical::Canonical for Receipt {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self { note_id } = self;
+        note_id.write_canonical(out);
+    }
+}
+
+impl canonical::Signable for Receipt {
+    const DOMAIN: &'static str = "n2p/receipt/v1";
+}
+
+impl canonical::Canonical for Part {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self {
+            note_id,
+            index,
+            count,
+            data,
+        } = self;
+        note_id.write_canonical(out);
+        index.write_canonical(out);
+        count.write_canonical(out);
+        data.write_canonical(out);
+    }
+}
+
+impl canonical::Signable for Part {
+    const DOMAIN: &'static str = "n2p/part/v1";
+}
+
+impl canonical::Canonical for Profile {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self {
+            display_name,
+            status,
+            avatar_hash,
+            updated_at,
+        } = self;
+        display_name.write_canonical(out);
+        status.write_canonical(out);
+        avatar_hash.write_canonical(out);
+        updated_at.write_canonical(out);
+    }
+}
+
+impl canonical::Signable for Profile {
+    const DOMAIN: &'static str = "n2p/profile/v1";
+}
+
+impl canonical::Canonical for Rotation {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self {
+            new_key,
+            rotated_at,
+        } = self;
+        new_key.write_canonical(out);
+        rotated_at.write_canonical(out);
+    }
+}
+
+impl canonical::Signable for Rotation {
+    const DOMAIN: &'static str = "n2p/rotation/v1";
+}
+
+impl canonical::Canonical for Revocation {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self { revoked_at, reason } = self;
+        revoked_at.write_canonical(out);
+        reason.write_canonical(out);
+    }
+}
+
+impl canonical::Signable for Revocation {
+    const DOMAIN: &'static str = "n2p/revocation/v1";
+}
+
+impl canonical::Canonical for Device {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self {
+            membership,
+            name,
+            issued_at,
+        } = self;
+        membership.write_canonical(out);
+        name.write_canonical(out);
+        issued_at.write_canonical(out);
+    }
+}
+
+impl canonical::Signable for Device {
+    const DOMAIN: &'static str = "n2p/device/v2";
+}
+
+impl canonical::Canonical for Membership {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self { identity } = self;
+        identity.write_canonical(out);
+    }
+}
+
+impl canonical::Signable for Membership {
+    const DOMAIN: &'static str = "n2p/membership/v1";
+}
+
+impl canonical::Canonical for PubKey {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        self.0.write_canonical(out);
+    }
+}
+
+impl<T: canonical::Canonical> canonical::Canonical for Signed<T> {
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        let Self {
+            inner,
+            pub_key,
+            signature,
+        } = self;
+        inner.write_canonical(out);
+        pub_key.write_canonical(out);
+        signature.write_canonical(out);
+    }
 }
 #[derive(
     Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
@@ -60,6 +463,70 @@ pub enum SignError<DigestError> {
 #[serde(try_from = "Vec<u8>")]
 pub struct PubKey(identity::PublicKey);
 
+impl PubKey {
+    /// Enough of the key to tell authors apart at a glance.
+    pub fn short(&self) -> String {
+        self.to_string().chars().take(8).collect()
+    }
+
+    /// How SSH shows the key, so it can be checked against a key people already trust. Only for Ed25519 keys.
+    pub fn ssh_fingerprint(&self) -> Option<String> {
+        self.ed25519_bytes().map(ssh::fingerprint)
+    }
+
+    /// The raw key, if it's an Ed25519 one.
+    pub fn ed25519_bytes(&self) -> Option<[u8; 32]> {
+        self.0
+            .clone()
+            .try_into_ed25519()
+            .ok()
+            .map(|key| key.to_bytes())
+    }
+}
+
+/// Hex of the raw key bytes, so the same key always reads the same regardless of its encoding.
+impl std::fmt::Display for PubKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes = match self.ed25519_bytes() {
+            Some(bytes) => bytes.to_vec(),
+            None => self.0.encode_protobuf(),
+        };
+        for byte in bytes {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads back what [`Display`](std::fmt::Display) writes for Ed25519 keys.
+impl std::str::FromStr for PubKey {
+    type Err = ParsePubKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(ParsePubKeyError);
+        }
+        let mut bytes = [0; 32];
+        for (byte, digits) in bytes.iter_mut().zip(s.as_bytes().chunks(2)) {
+            let digits = std::str::from_utf8(digits).map_err(|_| ParsePubKeyError)?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| ParsePubKeyError)?;
+        }
+        let key =
+            identity::ed25519::PublicKey::try_from_bytes(&bytes).map_err(|_| ParsePubKeyError)?;
+        Ok(PubKey(key.into()))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("expected the 64 hex digits of an Ed25519 key")]
+pub struct ParsePubKeyError;
+
+impl From<identity::PublicKey> for PubKey {
+    fn from(value: identity::PublicKey) -> Self {
+        PubKey(value)
+    }
+}
+
 impl From<PubKey> for Vec<u8> {
     fn from(value: PubKey) -> Self {
         value.0.encode_protobuf()
@@ -89,6 +556,16 @@ pub trait Decode: Sized {
     fn decode(reader: impl io::Read) -> Result<Self, Self::Error>;
 }
 
+/// Marks compressed encodings of notes. A plain one can't start like this: it starts with the length of the topic,
+/// which would have to be over a GiB.
+pub const COMPRESSED: &[u8; 4] = b"\xffn2z";
+
+/// Shorter encodings hardly shrink, so they aren't worth the time.
+const MIN_COMPRESSED_SIZE: usize = 256;
+
+/// Refuse to inflate past this, so a small message can't take all our memory. No note is larger.
+const MAX_DECOMPRESSED_SIZE: u64 = parts::MAX_NOTE_SIZE as u64;
+
 impl<T: serde::Serialize> Encode for T {
     type Error = bincode::Error;
     fn encode(&self, writer: impl io::Write) -> Result<(), Self::Error> {
@@ -103,35 +580,97 @@ impl<T: serde::de::DeserializeOwned> Decode for T {
     }
 }
 
-pub trait DigestHash {
-    type Error;
-    fn digest_hash(&self) -> Result<[u8; 32], Self::Error>;
-}
+impl Signed<Note> {
+    /// Deflated behind [`COMPRESSED`], if that's smaller than the plain encoding. For the wire and the store only,
+    /// signatures are over the [canonical](crate::canonical) encoding either way.
+    pub fn encode_compressed(&self) -> Result<Vec<u8>, bincode::Error> {
+        let plain = self.encode_to_vec()?;
+        if plain.len() < MIN_COMPRESSED_SIZE {
+            return Ok(plain);
+        }
+
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(COMPRESSED.to_vec(), flate2::Compression::default());
+        io::Write::write_all(&mut encoder, &plain)?;
+        let compressed = encoder.finish()?;
+
+        Ok(if compressed.len() < plain.len() {
+            compressed
+        } else {
+            plain
+        })
+    }
 
-impl<T: Encode> DigestHash for T {
-    type Error = T::Error;
+    /// Takes plain and compressed encodings alike.
+    pub fn decode_compressed(data: &[u8]) -> Result<Self, bincode::Error> {
+        let Some(compressed) = data.strip_prefix(COMPRESSED.as_slice()) else {
+            return Self::decode(data);
+        };
 
-    fn digest_hash(&self) -> Result<[u8; 32], Self::Error> {
-        let mut hasher = sha3::Keccak256::new();
+        let mut plain = Vec::new();
+        io::Read::read_to_end(
+            &mut io::Read::take(
+                flate2::read::DeflateDecoder::new(compressed),
+                MAX_DECOMPRESSED_SIZE + 1,
+            ),
+            &mut plain,
+        )?;
+        if plain.len() as u64 > MAX_DECOMPRESSED_SIZE {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidData, "too large once inflated").into(),
+            );
+        }
+
+        Self::decode(plain.as_slice())
+    }
+}
 
-        hasher.update(self.encode_to_vec()?);
+/// What is signed: Keccak256 of the domain and the canonical encoding.
+pub trait DigestHash {
+    fn digest_hash(&self) -> [u8; 32];
+}
 
-        Ok(hasher.finalize().into())
+impl<T: canonical::Signable> DigestHash for T {
+    fn digest_hash(&self) -> [u8; 32] {
+        canonical::hash(T::DOMAIN, self)
     }
 }
 
 use std::io;
 
 use libp2p::identity;
-use sha3::Digest;
+use sha3::Digest as _;
+
+use crate::canonical;
+use crate::hlc;
+use crate::keys;
+use crate::parts;
+use crate::ssh;
+use crate::verify;
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
     use fake::Fake;
     use rand::{RngCore, SeedableRng};
 
+    /// A note in `topic` written at `created_at`, with nothing before it.
+    pub(crate) fn note_with(
+        topic: &str,
+        msg: impl Into<String>,
+        created_at: time::OffsetDateTime,
+    ) -> Note {
+        Note {
+            topic: topic.to_string(),
+            msg: msg.into(),
+            created_at,
+            hlc: hlc::Timestamp::from_wall_clock(created_at),
+            parents: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
     #[test]
     fn notes_can_be_signed_and_verified() {
         let mut rng = rand::rngs::StdRng::seed_from_u64(42);
@@ -151,6 +690,41 @@ mod tests {
         assert!(!signed.verify());
     }
 
+    #[test]
+    fn compressed_notes_should_decode_like_plain_ones() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let now = time::OffsetDateTime::now_utc();
+        let note = |msg: &str| {
+            note_with("ops", msg, now)
+                .sign(&keypair)
+                .expect("failed to sign note")
+        };
+        let long = note(&"Deploying the release to production. ".repeat(100));
+        let short = note("ok");
+
+        let compressed = long.encode_compressed().expect("failed to encode");
+        assert!(compressed.starts_with(COMPRESSED));
+        assert!(compressed.len() < long.encode_to_vec().expect("failed to encode").len() / 4);
+        assert_eq!(
+            Signed::<Note>::decode_compressed(&compressed).ok(),
+            Some(long.clone())
+        );
+        assert!(Signed::<Note>::decode_compressed(&compressed)
+            .expect("failed to decode")
+            .verify());
+
+        // Not worth it for short notes, which then stay as they were.
+        let plain = short.encode_to_vec().expect("failed to encode");
+        assert_eq!(short.encode_compressed().ok(), Some(plain.clone()));
+        assert_eq!(Signed::<Note>::decode_compressed(&plain).ok(), Some(short));
+
+        // However well it compresses, a note too large to send doesn't inflate.
+        let too_long = note(&"x".repeat(parts::MAX_NOTE_SIZE));
+        let compressed = too_long.encode_compressed().expect("failed to encode");
+        assert!(compressed.len() < 64 * 1024);
+        assert!(Signed::<Note>::decode_compressed(&compressed).is_err());
+    }
+
     #[test]
     fn notes_can_be_encoded_and_decoded() {
         let mut rng = rand::rngs::StdRng::seed_from_u64(42);
@@ -171,4 +745,57 @@ mod tests {
 
         assert_eq!(decoded, signed);
     }
+
+    #[test]
+    fn device_notes_should_verify_as_their_identity() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let root = identity::Keypair::generate_ed25519();
+        let laptop = identity::Keypair::generate_ed25519();
+        let root_key: PubKey = root.public().into();
+        let laptop_key: PubKey = laptop.public().into();
+        let note = fake::Faker
+            .fake_with_rng::<Note, _>(&mut rng)
+            .sign(&laptop)
+            .expect("failed to sign note");
+
+        let mut keys = keys::Keys::new();
+        assert_eq!(note.verify_chain(&keys), Some(&laptop_key));
+
+        keys.add(Certificate::Device(
+            Device {
+                membership: Box::new(
+                    Membership {
+                        identity: root_key.clone(),
+                    }
+                    .sign(&laptop)
+                    .expect("failed to sign membership"),
+                ),
+                name: "laptop".to_string(),
+                issued_at: time::OffsetDateTime::now_utc(),
+            }
+            .sign(&root)
+            .expect("failed to sign device"),
+        ));
+        assert_eq!(note.verify_chain(&keys), Some(&root_key));
+
+        let mut tampered = note.clone();
+        tampered.inner.msg.push_str("TAMPERED");
+        assert_eq!(tampered.verify_chain(&keys), None);
+    }
+
+    #[test]
+    fn pub_keys_should_parse_from_how_they_are_shown() {
+        let pub_key: PubKey = identity::Keypair::generate_ed25519().public().into();
+
+        assert_eq!(
+            pub_key.to_string().parse::<PubKey>().ok(),
+            Some(pub_key.clone())
+        );
+        assert_eq!(
+            pub_key.to_string().to_uppercase().parse::<PubKey>().ok(),
+            Some(pub_key.clone())
+        );
+        assert!(pub_key.short().parse::<PubKey>().is_err());
+        assert!("zz".repeat(32).parse::<PubKey>().is_err());
+    }
 }
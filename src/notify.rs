@@ -0,0 +1,66 @@
+/// Whether `msg` calls us by `name`, or by the start of our key. Case and punctuation don't matter, so `@Alice,`
+/// mentions Alice, but `Alicetown` doesn't.
+pub fn mentions(msg: &str, name: &str, pub_key: &note::PubKey) -> bool {
+    let words: Vec<_> = search::terms(msg).collect();
+    let name: Vec<_> = search::terms(name).collect();
+    let key = pub_key.to_string();
+
+    (!name.is_empty() && words.windows(name.len()).any(|window| window == name))
+        || words
+            .iter()
+            .any(|word| word.len() >= MIN_KEY_PREFIX_LEN && key.starts_with(word.as_str()))
+}
+
+/// As long as [`note::PubKey::short`], so the key shown next to a note is enough to mention its author.
+const MIN_KEY_PREFIX_LEN: usize = 8;
+
+/// A local command run whenever someone mentions us, for desktop notifications or the like.
+///
+/// The command gets the note in `N2P_TOPIC`, `N2P_AUTHOR` and `N2P_MESSAGE`, and is left to run on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    pub command: path::PathBuf,
+}
+
+impl Hook {
+    pub fn run(&self, topic: &str, author: &str, msg: &str) -> io::Result<()> {
+        tokio::process::Command::new(&self.command)
+            .env("N2P_TOPIC", topic)
+            .env("N2P_AUTHOR", author)
+            .env("N2P_MESSAGE", msg)
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .spawn()?;
+
+        Ok(())
+    }
+}
+
+use std::io;
+use std::path;
+use std::process;
+
+use crate::note;
+use crate::search;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_should_match_whole_names_and_key_prefixes() {
+        let pub_key: note::PubKey = libp2p::identity::Keypair::generate_ed25519()
+            .public()
+            .into();
+        let key = pub_key.to_string();
+
+        assert!(mentions("@alice, look at this", "Alice", &pub_key));
+        assert!(mentions("Ask Alice Smith!", "alice smith", &pub_key));
+        assert!(!mentions("Ask Alice Jones", "Alice Smith", &pub_key));
+        assert!(!mentions("Welcome to Alicetown", "Alice", &pub_key));
+        assert!(!mentions("Hi everyone", "", &pub_key));
+        assert!(mentions(&format!("cc {}", &key[..8]), "", &pub_key));
+        assert!(!mentions(&format!("cc {}", &key[..4]), "", &pub_key));
+    }
+}
@@ -0,0 +1,326 @@
+//! Notes too large for one message travel as a sequence of [`note::Part`]s, each small enough and signed.
+//!
+//! Parts only carry slices of the encoded note, so the reassembled note is verified like any other.
+
+/// Hard cap on encoded notes, however many parts they would fit in.
+pub const MAX_NOTE_SIZE: usize = 1024 * 1024;
+
+/// Room for everything in a signed part besides its data.
+pub const PART_OVERHEAD: usize = 256;
+
+/// How many notes may wait for their remaining parts at once.
+const MAX_PENDING: usize = 64;
+
+/// How many of those may be by one author. Keys cost nothing, so this alone doesn't keep anyone from filling up.
+const MAX_PENDING_PER_AUTHOR: usize = 8;
+
+/// How many of those may have started with a part from one peer, however many authors they are by.
+const MAX_PENDING_PER_SOURCE: usize = 16;
+
+/// Parts of an encoded note, plain or compressed, each fitting in `max_message_size` once encoded.
+pub fn split(
+    note_id: note::NoteId,
+    encoded: &[u8],
+    max_message_size: usize,
+    signer: &impl note::Signer,
+) -> Result<Vec<note::Signed<note::Part>>, Error> {
+    if encoded.len() > MAX_NOTE_SIZE {
+        return Err(Error::TooLarge {
+            size: encoded.len(),
+            max: MAX_NOTE_SIZE,
+        });
+    }
+
+    let part_size = max_message_size.saturating_sub(PART_OVERHEAD).max(1);
+    let chunks: Vec<_> = encoded.chunks(part_size).collect();
+    let count = u16::try_from(chunks.len()).map_err(|_| Error::TooLarge {
+        size: encoded.len(),
+        max: part_size * usize::from(u16::MAX),
+    })?;
+
+    chunks
+        .into_iter()
+        .zip(0..)
+        .map(|(data, index)| {
+            note::Part {
+                note_id,
+                index,
+                count,
+                data: data.to_vec(),
+            }
+            .sign(signer)
+            .map_err(Error::from)
+        })
+        .collect()
+}
+
+/// Parts of notes we don't have all parts of yet, by author and note.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Reassembly {
+    pending: BTreeMap<(note::PubKey, note::NoteId), Pending>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pending {
+    count: u16,
+    parts: BTreeMap<u16, Vec<u8>>,
+    size: usize,
+    first_seen: Instant,
+    /// The peer the first part came from.
+    source: libp2p::PeerId,
+}
+
+impl Reassembly {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The note, once the part completes it. The part must be verified, the note still has to be.
+    ///
+    /// A new note is only waited for while its author and `source`, the peer the part came from, have room left.
+    pub fn add(
+        &mut self,
+        part: note::Signed<note::Part>,
+        source: libp2p::PeerId,
+        now: Instant,
+    ) -> Result<Option<note::Signed<note::Note>>, Error> {
+        let key = (part.pub_key.clone(), part.inner.note_id);
+        // Everything we have of a note is dropped when its parts don't fit, so nothing of it is left to relay.
+        if part.inner.index >= part.inner.count {
+            self.pending.remove(&key);
+            return Err(Error::Malformed);
+        }
+        if !self.pending.contains_key(&key) {
+            let by_author = self
+                .pending
+                .keys()
+                .filter(|(pub_key, _)| *pub_key == part.pub_key)
+                .count();
+            let by_source = self
+                .pending
+                .values()
+                .filter(|pending| pending.source == source)
+                .count();
+            if self.pending.len() >= MAX_PENDING
+                || by_author >= MAX_PENDING_PER_AUTHOR
+                || by_source >= MAX_PENDING_PER_SOURCE
+            {
+                return Err(Error::Busy);
+            }
+        }
+
+        let pending = self.pending.entry(key.clone()).or_insert_with(|| Pending {
+            count: part.inner.count,
+            parts: BTreeMap::new(),
+            size: 0,
+            first_seen: now,
+            source,
+        });
+        if pending.count != part.inner.count {
+            self.pending.remove(&key);
+            return Err(Error::Malformed);
+        }
+        if !pending.parts.contains_key(&part.inner.index) {
+            pending.size += part.inner.data.len();
+            pending.parts.insert(part.inner.index, part.inner.data);
+        }
+        if pending.size > MAX_NOTE_SIZE {
+            let size = pending.size;
+            self.pending.remove(&key);
+            return Err(Error::TooLarge {
+                size,
+                max: MAX_NOTE_SIZE,
+            });
+        }
+        if pending.parts.len() < usize::from(pending.count) {
+            return Ok(None);
+        }
+
+        let pending = self.pending.remove(&key).expect("pending note is there");
+        let encoded = pending.parts.into_values().collect::<Vec<_>>().concat();
+        let note = note::Signed::<note::Note>::decode_compressed(&encoded)?;
+        if (note.pub_key.clone(), note.id()) != key {
+            return Err(Error::Mismatch);
+        }
+
+        Ok(Some(note))
+    }
+
+    /// Gives up on notes whose parts didn't all arrive since `before`, and tells which those were.
+    pub fn prune(&mut self, before: Instant) -> Vec<(note::PubKey, note::NoteId)> {
+        let (expired, pending): (BTreeMap<_, _>, _) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(_, pending)| pending.first_seen < before);
+        self.pending = pending;
+        expired.into_keys().collect()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("note is {size} bytes, but at most {max} bytes are allowed")]
+    TooLarge { size: usize, max: usize },
+    #[error("part doesn't fit the other parts of its note")]
+    Malformed,
+    #[error("parts don't add up to the note they are for")]
+    Mismatch,
+    #[error("too many notes waiting for parts, from this author or peer or overall")]
+    Busy,
+    #[error("encoding error")]
+    Encoding(#[from] bincode::Error),
+    #[error("signing error")]
+    Signing(#[from] note::SignError),
+}
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crate::note;
+use crate::note::Sign as _;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libp2p::identity;
+    use note::Encode as _;
+
+    fn long_note(key_pair: &identity::Keypair, len: usize) -> note::Signed<note::Note> {
+        note::tests::note_with(
+            "paste",
+            "All work and no play. ".repeat(len / 22 + 1),
+            time::OffsetDateTime::now_utc(),
+        )
+        .sign(key_pair)
+        .expect("failed to sign note")
+    }
+
+    #[test]
+    fn parts_should_reassemble_in_any_order() {
+        let key_pair = identity::Keypair::generate_ed25519();
+        let note = long_note(&key_pair, 10_000);
+
+        let encoded = note.encode_to_vec().expect("failed to encode note");
+        let mut parts = split(note.id(), &encoded, 1024, &key_pair).expect("failed to split note");
+        assert!(parts.len() > 10);
+        assert!(parts
+            .iter()
+            .all(|part| part.verify() && part.encode_to_vec().unwrap().len() <= 1024));
+
+        parts.reverse();
+        let duplicate = parts[0].clone();
+        let mut reassembly = Reassembly::new();
+        let source = libp2p::PeerId::random();
+        let now = Instant::now();
+        let last = parts.pop().expect("no parts");
+        for part in parts.into_iter().chain([duplicate]) {
+            assert!(reassembly
+                .add(part, source, now)
+                .expect("invalid part")
+                .is_none());
+        }
+
+        assert_eq!(
+            reassembly.add(last, source, now).expect("invalid part"),
+            Some(note)
+        );
+        assert_eq!(reassembly, Reassembly::new());
+    }
+
+    #[test]
+    fn oversized_and_forged_parts_should_be_refused() {
+        let key_pair = identity::Keypair::generate_ed25519();
+        let forger = identity::Keypair::generate_ed25519();
+        let source = libp2p::PeerId::random();
+        let now = Instant::now();
+
+        let too_long = long_note(&key_pair, MAX_NOTE_SIZE);
+        let encoded = too_long.encode_to_vec().expect("failed to encode note");
+        assert!(matches!(
+            split(too_long.id(), &encoded, 64 * 1024, &key_pair),
+            Err(Error::TooLarge {
+                max: MAX_NOTE_SIZE,
+                ..
+            })
+        ));
+
+        // Someone else's parts for our note don't add up to it, even if they are right.
+        let note = long_note(&key_pair, 2_000);
+        let encoded = note.encode_to_vec().expect("failed to encode note");
+        let parts = split(note.id(), &encoded, 1024, &key_pair).expect("failed to split note");
+        let mut reassembly = Reassembly::new();
+        let mut result = Ok(None);
+        for part in parts {
+            let forged = part.inner.sign(&forger).expect("failed to sign part");
+            result = reassembly.add(forged, source, now);
+        }
+        assert!(matches!(result, Err(Error::Mismatch)));
+
+        let part = note::Part {
+            note_id: note.id(),
+            index: 3,
+            count: 3,
+            data: Vec::new(),
+        }
+        .sign(&key_pair)
+        .expect("failed to sign part");
+        assert!(matches!(
+            reassembly.add(part, source, now),
+            Err(Error::Malformed)
+        ));
+    }
+
+    #[test]
+    fn authors_and_peers_should_only_keep_so_many_notes_waiting() {
+        let now = Instant::now();
+        let first_part = |key_pair: &identity::Keypair| {
+            let note = long_note(key_pair, 2_000);
+            let encoded = note.encode_to_vec().expect("failed to encode note");
+            split(note.id(), &encoded, 1024, key_pair)
+                .expect("failed to split note")
+                .remove(0)
+        };
+        let mut reassembly = Reassembly::new();
+
+        let author = identity::Keypair::generate_ed25519();
+        for _ in 0..MAX_PENDING_PER_AUTHOR {
+            let part = first_part(&author);
+            assert!(matches!(
+                reassembly.add(part, libp2p::PeerId::random(), now),
+                Ok(None)
+            ));
+        }
+        let part = first_part(&author);
+        assert!(matches!(
+            reassembly.add(part, libp2p::PeerId::random(), now),
+            Err(Error::Busy)
+        ));
+
+        // Fresh keys don't get around it, one peer still only brings in so many notes.
+        let source = libp2p::PeerId::random();
+        for _ in 0..MAX_PENDING_PER_SOURCE {
+            let part = first_part(&identity::Keypair::generate_ed25519());
+            assert!(matches!(reassembly.add(part, source, now), Ok(None)));
+        }
+        let part = first_part(&identity::Keypair::generate_ed25519());
+        assert!(matches!(
+            reassembly.add(part, source, now),
+            Err(Error::Busy)
+        ));
+
+        let later = now + std::time::Duration::from_secs(1);
+        let part = first_part(&identity::Keypair::generate_ed25519());
+        let other = (part.pub_key.clone(), part.inner.note_id);
+        assert!(matches!(
+            reassembly.add(part, libp2p::PeerId::random(), later),
+            Ok(None)
+        ));
+        let expired = reassembly.prune(later);
+        assert_eq!(
+            expired.len(),
+            MAX_PENDING_PER_AUTHOR + MAX_PENDING_PER_SOURCE
+        );
+        assert!(!expired.contains(&other));
+        assert_eq!(reassembly.pending.len(), 1);
+    }
+}
@@ -0,0 +1,109 @@
+/// Allows `burst` events at once, and one more every `interval` after that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub interval: std::time::Duration,
+}
+
+/// A token bucket per key.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    limit: RateLimit,
+    buckets: HashMap<K, Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: time::Instant,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes a token for `key`. Returns false when there is none left.
+    pub fn check(&mut self, key: K, now: time::Instant) -> bool {
+        let limit = self.limit;
+        let bucket = self.buckets.entry(key).or_insert(Bucket {
+            tokens: limit.burst.into(),
+            updated_at: now,
+        });
+        bucket.refill(limit, now);
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// Forgets keys whose bucket is full again, since they start out full anyway.
+    pub fn prune(&mut self, now: time::Instant) {
+        let limit = self.limit;
+        self.buckets.retain(|_, bucket| {
+            bucket.refill(limit, now);
+            bucket.tokens < limit.burst.into()
+        });
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: RateLimit, now: time::Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at);
+        let refilled = if limit.interval.is_zero() {
+            f64::INFINITY
+        } else {
+            elapsed.as_secs_f64() / limit.interval.as_secs_f64()
+        };
+
+        self.tokens = (self.tokens + refilled).min(limit.burst.into());
+        self.updated_at = now;
+    }
+}
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_should_be_allowed_until_the_bucket_is_empty() {
+        let mut limiter = RateLimiter::new(RateLimit {
+            burst: 3,
+            interval: time::Duration::from_secs(1),
+        });
+        let now = time::Instant::now();
+
+        assert!((0..3).all(|_| limiter.check("flooder", now)));
+        assert!(!limiter.check("flooder", now));
+        assert!(limiter.check("someone else", now));
+
+        assert!(limiter.check("flooder", now + time::Duration::from_secs(1)));
+        assert!(!limiter.check("flooder", now + time::Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn pruning_should_only_forget_full_buckets() {
+        let mut limiter = RateLimiter::new(RateLimit {
+            burst: 2,
+            interval: time::Duration::from_secs(1),
+        });
+        let now = time::Instant::now();
+
+        limiter.check("recent", now + time::Duration::from_secs(10));
+        limiter.check("old", now);
+        limiter.prune(now + time::Duration::from_secs(10));
+
+        assert_eq!(limiter.buckets.len(), 1);
+        assert!(limiter.buckets.contains_key("recent"));
+    }
+}
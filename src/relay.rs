@@ -0,0 +1,104 @@
+pub struct Relay {
+    swarm: libp2p::Swarm<Behavior>,
+    advertise_listen_addrs: bool,
+}
+
+#[derive(libp2p::swarm::NetworkBehaviour)]
+struct Behavior {
+    relay: libp2p::relay::Behaviour,
+    identify: libp2p::identify::Behaviour,
+    kademlia: libp2p::kad::Behaviour<libp2p::kad::store::MemoryStore>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub listen_addrs: Vec<libp2p::Multiaddr>,
+    /// Addresses handed out to clients in their reservations. Defaults to our listen addresses.
+    pub external_addrs: Vec<libp2p::Multiaddr>,
+    pub swarm_key: Option<libp2p::pnet::PreSharedKey>,
+    /// Decides our peer ID, so keep it across restarts for the addresses clients are given to stay valid.
+    pub key: libp2p::identity::Keypair,
+}
+
+impl Relay {
+    pub fn new(config: Config) -> anyhow::Result<Self> {
+        let mut swarm = libp2p::SwarmBuilder::with_existing_identity(config.key)
+            .with_tokio()
+            .with_other_transport(|key| {
+                controller::transport(key, config.swarm_key).map_err(Into::into)
+            })
+            .context("failed to configure transport for swarm")?
+            .with_behaviour(|key| Behavior {
+                relay: libp2p::relay::Behaviour::new(
+                    key.public().to_peer_id(),
+                    libp2p::relay::Config::default(),
+                ),
+                identify: controller::identify_behaviour(key),
+                // Relays are reachable by definition, which makes them good DHT bootstrap peers.
+                kademlia: controller::kademlia_behaviour(key),
+            })
+            .context("failed to configure behavior for swarm")?
+            .with_swarm_config(|c| {
+                c.with_idle_connection_timeout(std::time::Duration::from_secs(60))
+            })
+            .build();
+
+        for addr in config.listen_addrs {
+            swarm
+                .listen_on(addr.clone())
+                .with_context(|| format!("failed to listen on {addr}"))?;
+        }
+
+        let advertise_listen_addrs = config.external_addrs.is_empty();
+        for addr in config.external_addrs {
+            swarm.add_external_address(addr);
+        }
+
+        Ok(Self {
+            swarm,
+            advertise_listen_addrs,
+        })
+    }
+
+    pub async fn run(&mut self) -> anyhow::Result<()> {
+        loop {
+            self.poll().await;
+        }
+    }
+
+    pub async fn poll(&mut self) {
+        match self.swarm.select_next_some().await {
+            libp2p::swarm::SwarmEvent::NewListenAddr { address, .. }
+                if self.advertise_listen_addrs =>
+            {
+                self.swarm.add_external_address(address);
+            }
+
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviorEvent::Identify(
+                libp2p::identify::Event::Received { peer_id, info },
+            )) if info.protocols.contains(&controller::KADEMLIA_PROTOCOL) => {
+                for addr in info.listen_addrs {
+                    self.swarm
+                        .behaviour_mut()
+                        .kademlia
+                        .add_address(&peer_id, addr);
+                }
+            }
+
+            _other => {}
+        }
+    }
+
+    pub fn local_peer_id(&self) -> libp2p::PeerId {
+        *self.swarm.local_peer_id()
+    }
+
+    pub fn listen_addrs(&self) -> Vec<libp2p::Multiaddr> {
+        self.swarm.listeners().cloned().collect()
+    }
+}
+
+use anyhow::Context;
+use libp2p::futures::StreamExt as _;
+
+use crate::controller;
@@ -0,0 +1,229 @@
+/// Inverted index over note text, kept up to date as notes come in.
+///
+/// Authors are indexed by key rather than name, so renaming a contact doesn't need a reindex.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Index {
+    terms: BTreeMap<String, BTreeSet<note::NoteId>>,
+    authors: BTreeMap<note::PubKey, BTreeSet<note::NoteId>>,
+    locations: BTreeMap<note::NoteId, Location>,
+}
+
+/// Where to find an indexed note in [`crate::model::Model::topics`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub topic: String,
+    pub key: (hlc::Timestamp, note::NoteId),
+}
+
+impl Index {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_note(&mut self, note: &note::Signed<note::Note>) {
+        let id = note.id();
+        for term in terms(&note.inner.msg) {
+            self.terms.entry(term).or_default().insert(id);
+        }
+        self.authors
+            .entry(note.pub_key.clone())
+            .or_default()
+            .insert(id);
+        self.locations.insert(
+            id,
+            Location {
+                topic: note.inner.topic.clone(),
+                key: (note.inner.hlc, id),
+            },
+        );
+    }
+
+    pub fn remove_author(&mut self, pub_key: &note::PubKey) {
+        let Some(ids) = self.authors.remove(pub_key) else {
+            return;
+        };
+
+        self.terms.retain(|_, notes| {
+            notes.retain(|id| !ids.contains(id));
+            !notes.is_empty()
+        });
+        for id in ids {
+            self.locations.remove(&id);
+        }
+    }
+
+    pub fn location(&self, id: &note::NoteId) -> Option<&Location> {
+        self.locations.get(id)
+    }
+
+    /// Notes with a word starting with `prefix`, so results show up while the word is still being typed.
+    pub fn matching(&self, prefix: &str) -> BTreeSet<note::NoteId> {
+        self.terms
+            .range(prefix.to_string()..)
+            .take_while(|(term, _)| term.starts_with(prefix))
+            .flat_map(|(_, notes)| notes.iter().copied())
+            .collect()
+    }
+
+    pub fn by_author(&self, pub_key: &note::PubKey) -> BTreeSet<note::NoteId> {
+        self.authors.get(pub_key).cloned().unwrap_or_default()
+    }
+
+    pub fn has_author(&self, pub_key: &note::PubKey) -> bool {
+        self.authors.contains_key(pub_key)
+    }
+
+    pub fn authors(&self) -> impl Iterator<Item = &note::PubKey> {
+        self.authors.keys()
+    }
+
+    pub fn all(&self) -> BTreeSet<note::NoteId> {
+        self.locations.keys().copied().collect()
+    }
+}
+
+/// Words to search for, plus filters like `topic:rust from:alice before:2024-06-01 after:2024-05-01`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub terms: Vec<String>,
+    pub topic: Option<String>,
+    /// Matches the name we show for an author, or the start of their key.
+    pub from: Option<String>,
+    pub before: Option<time::OffsetDateTime>,
+    pub after: Option<time::OffsetDateTime>,
+}
+
+impl Query {
+    /// Dates are days in `local_offset`.
+    pub fn parse(text: &str, local_offset: time::UtcOffset) -> Result<Self, ParseError> {
+        let mut query = Self::default();
+
+        for word in text.split_whitespace() {
+            match word.split_once(':') {
+                Some(("topic", topic)) => query.topic = Some(topic.to_lowercase()),
+                Some(("from", from)) => query.from = Some(from.to_lowercase()),
+                Some(("before", date)) => query.before = Some(parse_date(date, local_offset)?),
+                Some(("after", date)) => query.after = Some(parse_date(date, local_offset)?),
+                _ => query.terms.extend(terms(word)),
+            }
+        }
+
+        Ok(query)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("invalid date {0}, expected YYYY-MM-DD")]
+    InvalidDate(String),
+}
+
+/// Start of the day.
+fn parse_date(date: &str, offset: time::UtcOffset) -> Result<time::OffsetDateTime, ParseError> {
+    let invalid = || ParseError::InvalidDate(date.to_string());
+    let mut parts = date.splitn(3, '-');
+    let mut next = || parts.next().and_then(|part| part.parse::<i32>().ok());
+    let (Some(year), Some(month), Some(day)) = (next(), next(), next()) else {
+        return Err(invalid());
+    };
+
+    let month = u8::try_from(month)
+        .ok()
+        .and_then(|month| time::Month::try_from(month).ok())
+        .ok_or_else(invalid)?;
+    let day = u8::try_from(day).map_err(|_| invalid())?;
+    let date = time::Date::from_calendar_date(year, month, day).map_err(|_| invalid())?;
+
+    Ok(date.midnight().assume_offset(offset))
+}
+
+/// Lowercase words, split on anything that isn't a letter or digit.
+pub fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+}
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use crate::hlc;
+use crate::note;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::model;
+    use note::Sign as _;
+
+    #[test]
+    fn queries_should_split_filters_from_terms() {
+        let query = Query::parse(
+            "Deploy topic:Ops from:alice before:2024-06-01 after:2024-05-01 friday!",
+            time::UtcOffset::UTC,
+        )
+        .expect("failed to parse query");
+
+        assert_eq!(
+            query,
+            Query {
+                terms: vec!["deploy".to_string(), "friday".to_string()],
+                topic: Some("ops".to_string()),
+                from: Some("alice".to_string()),
+                before: Some(time::macros::datetime!(2024-06-01 0:00 UTC)),
+                after: Some(time::macros::datetime!(2024-05-01 0:00 UTC)),
+            }
+        );
+        assert_eq!(
+            Query::parse("before:yesterday", time::UtcOffset::UTC),
+            Err(ParseError::InvalidDate("yesterday".to_string()))
+        );
+    }
+
+    #[test]
+    fn search_should_match_prefixes_petnames_and_filters() {
+        let alice = libp2p::identity::Keypair::generate_ed25519();
+        let bob = libp2p::identity::Keypair::generate_ed25519();
+        let may = time::macros::datetime!(2024-05-10 12:00 UTC);
+        let june = time::macros::datetime!(2024-06-10 12:00 UTC);
+        let note = |key_pair: &libp2p::identity::Keypair, topic: &str, msg: &str, created_at| {
+            note::tests::note_with(topic, msg, created_at)
+                .sign(key_pair)
+                .expect("failed to sign note")
+        };
+
+        let mut model = model::Model::new();
+        let deploy = note(&alice, "ops", "Deploying on Friday", may);
+        let rollback = note(&bob, "ops", "Rolled back the deployment", june);
+        let lunch = note(&bob, "random", "Lunch?", june + time::Duration::hours(1));
+        for note in [&deploy, &rollback, &lunch] {
+            model.add_note(note.clone());
+        }
+        model.contacts.insert(
+            bob.public().into(),
+            model::Contact {
+                petname: "Bob".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let search = |text| {
+            model
+                .search(&Query::parse(text, time::UtcOffset::UTC).expect("failed to parse query"))
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(search("deploy"), vec![rollback.clone(), deploy.clone()]);
+        assert_eq!(search("deploy from:bob"), vec![rollback.clone()]);
+        assert_eq!(search("deploy before:2024-06-01"), vec![deploy.clone()]);
+        assert_eq!(search("bob"), vec![lunch.clone(), rollback.clone()]);
+        assert_eq!(search("after:2024-06-01 topic:random"), vec![lunch]);
+    }
+}
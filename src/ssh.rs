@@ -0,0 +1,318 @@
+//! Signing with the Ed25519 keys people already have for SSH, from their private key file or from their SSH agent.
+//!
+//! Ed25519 signatures are the same whoever makes them, so notes signed with an SSH key verify like any other.
+
+/// The key pair in an OpenSSH private key file. `passphrase` is only asked for if the key is encrypted.
+pub fn read_key(
+    path: &Path,
+    passphrase: impl FnOnce() -> io::Result<String>,
+) -> Result<identity::Keypair, Error> {
+    let mut key = ssh_key::PrivateKey::read_openssh_file(path)?;
+    if key.is_encrypted() {
+        key = key.decrypt(passphrase()?)?;
+    }
+
+    let Some(key_pair) = key.key_data().ed25519() else {
+        return Err(Error::UnsupportedKey(key.algorithm().to_string()));
+    };
+    Ok(
+        identity::Keypair::ed25519_from_bytes(key_pair.private.to_bytes())
+            .expect("Ed25519 private keys are 32 bytes"),
+    )
+}
+
+/// `SHA256:` and the base64 digest, like `ssh-keygen -l` shows.
+pub fn fingerprint(ed25519_key: [u8; 32]) -> String {
+    public_key(ed25519_key)
+        .fingerprint(ssh_key::HashAlg::Sha256)
+        .to_string()
+}
+
+fn public_key(ed25519_key: [u8; 32]) -> ssh_key::PublicKey {
+    ssh_key::PublicKey::new(
+        ssh_key::public::KeyData::Ed25519(ssh_key::public::Ed25519PublicKey(ed25519_key)),
+        "",
+    )
+}
+
+/// An Ed25519 key held by an SSH agent, which signs for us without handing out the private key.
+#[derive(Debug, Clone)]
+pub struct Agent {
+    socket: PathBuf,
+    key: [u8; 32],
+}
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+/// Agents answer in a few bytes per key, anything much longer isn't an agent.
+const MAX_AGENT_MESSAGE_LEN: u32 = 256 * 1024;
+
+impl Agent {
+    /// The agent listening on `socket`, usually `SSH_AUTH_SOCK`. Uses the key with `fingerprint`, or the first
+    /// Ed25519 key the agent has.
+    pub fn connect(socket: impl Into<PathBuf>, fingerprint: Option<&str>) -> Result<Self, Error> {
+        let socket = socket.into();
+        let answer = request(&socket, &[SSH_AGENTC_REQUEST_IDENTITIES])?;
+        let mut answer = answer.as_slice();
+        if read_u8(&mut answer)? != SSH_AGENT_IDENTITIES_ANSWER {
+            return Err(Error::Agent);
+        }
+
+        for _ in 0..read_u32(&mut answer)? {
+            let blob = read_string(&mut answer)?;
+            read_string(&mut answer)?;
+            let Some(key) = ssh_key::PublicKey::from_bytes(blob)
+                .ok()
+                .and_then(|key| key.key_data().ed25519().map(|key| key.0))
+                .filter(|key| identity::ed25519::PublicKey::try_from_bytes(key).is_ok())
+            else {
+                continue;
+            };
+
+            if fingerprint.is_none_or(|fingerprint| self::fingerprint(key) == fingerprint) {
+                return Ok(Self { socket, key });
+            }
+        }
+
+        Err(Error::NoKey)
+    }
+
+    pub fn public(&self) -> identity::PublicKey {
+        identity::ed25519::PublicKey::try_from_bytes(&self.key)
+            .expect("agent keys are checked when connecting")
+            .into()
+    }
+
+    /// A plain Ed25519 signature of `message`.
+    pub fn sign(&self, message: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut request_message = vec![SSH_AGENTC_SIGN_REQUEST];
+        write_string(&mut request_message, &public_key(self.key).to_bytes()?);
+        write_string(&mut request_message, message);
+        request_message.extend(0u32.to_be_bytes());
+
+        let answer = request(&self.socket, &request_message)?;
+        let mut answer = answer.as_slice();
+        match read_u8(&mut answer)? {
+            SSH_AGENT_SIGN_RESPONSE => {}
+            SSH_AGENT_FAILURE => return Err(Error::Refused),
+            _ => return Err(Error::Agent),
+        }
+
+        let mut signature = read_string(&mut answer)?;
+        if read_string(&mut signature)? != b"ssh-ed25519" {
+            return Err(Error::Agent);
+        }
+        Ok(read_string(&mut signature)?.to_vec())
+    }
+}
+
+/// One request, on a connection of its own, so a restarted agent is picked up.
+///
+/// Agents may wait for the user to confirm, so on the runtime's threads its other tasks are moved off meanwhile.
+fn request(socket: &Path, message: &[u8]) -> Result<Vec<u8>, Error> {
+    let on_runtime = tokio::runtime::Handle::try_current().is_ok_and(|runtime| {
+        runtime.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread
+    });
+    if on_runtime {
+        tokio::task::block_in_place(|| request_blocking(socket, message))
+    } else {
+        request_blocking(socket, message)
+    }
+}
+
+fn request_blocking(socket: &Path, message: &[u8]) -> Result<Vec<u8>, Error> {
+    #[cfg(unix)]
+    {
+        exchange(std::os::unix::net::UnixStream::connect(socket)?, message)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (socket, message);
+        Err(Error::NoAgent)
+    }
+}
+
+/// Messages are length prefixed both ways.
+fn exchange(mut stream: impl io::Read + io::Write, message: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut framed = Vec::new();
+    write_string(&mut framed, message);
+    stream.write_all(&framed)?;
+
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_AGENT_MESSAGE_LEN {
+        return Err(Error::Agent);
+    }
+    let mut answer = vec![0; len as usize];
+    stream.read_exact(&mut answer)?;
+    Ok(answer)
+}
+
+fn write_string(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(data);
+}
+
+fn read_u8(data: &mut &[u8]) -> Result<u8, Error> {
+    let (&byte, rest) = data.split_first().ok_or(Error::Agent)?;
+    *data = rest;
+    Ok(byte)
+}
+
+fn read_u32(data: &mut &[u8]) -> Result<u32, Error> {
+    let Some((len, rest)) = data.split_first_chunk() else {
+        return Err(Error::Agent);
+    };
+    *data = rest;
+    Ok(u32::from_be_bytes(*len))
+}
+
+fn read_string<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let len = read_u32(data)? as usize;
+    if data.len() < len {
+        return Err(Error::Agent);
+    }
+    let (string, rest) = data.split_at(len);
+    *data = rest;
+    Ok(string)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("io error")]
+    Io(#[from] io::Error),
+    #[error("invalid SSH key")]
+    Key(#[from] ssh_key::Error),
+    #[error("{0} keys can't sign notes, only Ed25519 keys can")]
+    UnsupportedKey(String),
+    #[error("the SSH agent has no matching Ed25519 key")]
+    NoKey,
+    #[error("the SSH agent refused to sign")]
+    Refused,
+    #[error("unexpected answer from the SSH agent")]
+    Agent,
+    #[error("SSH agents are only supported on Unix")]
+    NoAgent,
+}
+
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use libp2p::identity;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng as _;
+
+    fn private_key(seed: [u8; 32]) -> ssh_key::PrivateKey {
+        ssh_key::PrivateKey::new(
+            ssh_key::private::KeypairData::Ed25519(ssh_key::private::Ed25519Keypair::from_seed(
+                &seed,
+            )),
+            "alice@example.com",
+        )
+        .expect("invalid key")
+    }
+
+    #[test]
+    fn openssh_keys_should_sign_like_the_same_key_pair() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let plain = dir.path().join("id_ed25519");
+        let encrypted = dir.path().join("id_ed25519_encrypted");
+        let key = private_key([7; 32]);
+        key.write_openssh_file(&plain, ssh_key::LineEnding::LF)
+            .expect("failed to write key");
+        key.encrypt(&mut rand::rngs::StdRng::seed_from_u64(42), "hunter2")
+            .expect("failed to encrypt key")
+            .write_openssh_file(&encrypted, ssh_key::LineEnding::LF)
+            .expect("failed to write key");
+
+        let expected = identity::Keypair::ed25519_from_bytes([7; 32]).expect("invalid key");
+        let from_plain = read_key(&plain, || panic!("plain keys need no passphrase"))
+            .expect("failed to read key");
+        let from_encrypted =
+            read_key(&encrypted, || Ok("hunter2".to_string())).expect("failed to read key");
+        assert_eq!(from_plain.public(), expected.public());
+        assert_eq!(from_encrypted.public(), expected.public());
+        assert!(read_key(&encrypted, || Ok("hunter3".to_string())).is_err());
+
+        assert_eq!(
+            fingerprint(key.public_key().key_data().ed25519().unwrap().0),
+            key.fingerprint(ssh_key::HashAlg::Sha256).to_string()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn agents_should_sign_with_the_key_they_hold() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let socket = dir.path().join("agent.sock");
+        let listener =
+            std::os::unix::net::UnixListener::bind(&socket).expect("failed to bind socket");
+        let key_pair = identity::Keypair::ed25519_from_bytes([7; 32]).expect("invalid key");
+        let key = private_key([7; 32]);
+
+        // Just enough of an agent: one key, three requests.
+        let agent_key_pair = key_pair.clone();
+        let agent = std::thread::spawn(move || {
+            for _ in 0..3 {
+                let (mut stream, _) = listener.accept().expect("failed to accept");
+                let mut len = [0; 4];
+                io::Read::read_exact(&mut stream, &mut len).unwrap();
+                let mut request = vec![0; u32::from_be_bytes(len) as usize];
+                io::Read::read_exact(&mut stream, &mut request).unwrap();
+
+                let mut answer = Vec::new();
+                let mut request = request.as_slice();
+                match read_u8(&mut request).unwrap() {
+                    SSH_AGENTC_REQUEST_IDENTITIES => {
+                        answer.push(SSH_AGENT_IDENTITIES_ANSWER);
+                        answer.extend(1u32.to_be_bytes());
+                        write_string(&mut answer, &key.public_key().to_bytes().unwrap());
+                        write_string(&mut answer, b"alice@example.com");
+                    }
+                    SSH_AGENTC_SIGN_REQUEST => {
+                        read_string(&mut request).unwrap();
+                        let message = read_string(&mut request).unwrap();
+                        let mut signature = Vec::new();
+                        write_string(&mut signature, b"ssh-ed25519");
+                        write_string(&mut signature, &agent_key_pair.sign(message).unwrap());
+                        answer.push(SSH_AGENT_SIGN_RESPONSE);
+                        write_string(&mut answer, &signature);
+                    }
+                    _ => answer.push(SSH_AGENT_FAILURE),
+                }
+                let mut framed = Vec::new();
+                write_string(&mut framed, &answer);
+                io::Write::write_all(&mut stream, &framed).unwrap();
+            }
+        });
+
+        let agent_key = Agent::connect(&socket, None).expect("failed to connect to agent");
+        let signature = agent_key.sign(b"hello").expect("failed to sign");
+        // The runtime's other tasks aren't held up waiting for the agent.
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .build()
+            .expect("failed to start runtime");
+        let on_runtime = agent_key.clone();
+        let signature_on_runtime = runtime
+            .block_on(async { tokio::spawn(async move { on_runtime.sign(b"hello") }).await })
+            .expect("signing task failed")
+            .expect("failed to sign");
+        agent.join().expect("agent failed");
+
+        assert_eq!(agent_key.public(), key_pair.public());
+        assert!(key_pair.public().verify(b"hello", &signature));
+        assert_eq!(signature_on_runtime, signature);
+        // Once the agent is gone, signing fails rather than hangs.
+        assert!(agent_key.sign(b"hello").is_err());
+    }
+}
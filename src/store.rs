@@ -0,0 +1,941 @@
+/// Local state that must survive restarts, kept as files in a data directory.
+///
+/// An in-memory store keeps nothing, which is what tests and throwaway sessions want.
+#[derive(Debug, Clone)]
+pub struct Store {
+    dir: Option<path::PathBuf>,
+}
+
+impl Store {
+    pub fn open(dir: impl Into<path::PathBuf>) -> Result<Self, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self { dir: Some(dir) })
+    }
+
+    pub fn in_memory() -> Self {
+        Self { dir: None }
+    }
+
+    pub fn load_outbox(&self) -> Result<Vec<note::Signed<note::Note>>, Error> {
+        Ok(self.load(OUTBOX_FILE)?.unwrap_or_default())
+    }
+
+    pub fn save_outbox<'a>(
+        &self,
+        notes: impl IntoIterator<Item = &'a note::Signed<note::Note>>,
+    ) -> Result<(), Error> {
+        let notes: Vec<_> = notes.into_iter().collect();
+        self.save(OUTBOX_FILE, &notes)
+    }
+
+    /// Every note we accepted, in the order we got them.
+    pub fn load_notes(&self) -> Result<Vec<note::Signed<note::Note>>, Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(Vec::new());
+        };
+
+        let path = dir.join(NOTES_FILE);
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut notes = Vec::new();
+        let mut records = bundle::read_records(io::BufReader::new(file));
+        loop {
+            match records.next() {
+                Some(Ok(record)) => {
+                    notes.extend(note::Signed::<note::Note>::decode_compressed(&record).ok());
+                }
+                // A crash while appending leaves a partial note at the end, which we can only drop. It's cut off
+                // the file too, or the notes appended after it couldn't be told apart from its remains.
+                Some(Err(bundle::Error::Truncated | bundle::Error::RecordTooLarge)) => {
+                    fs::OpenOptions::new()
+                        .write(true)
+                        .open(&path)?
+                        .set_len(records.position())?;
+                    break;
+                }
+                Some(Err(err)) => return Err(err.into()),
+                None => break,
+            }
+        }
+
+        Ok(notes)
+    }
+
+    pub fn append_notes<'a>(
+        &self,
+        notes: impl IntoIterator<Item = &'a note::Signed<note::Note>>,
+    ) -> Result<(), Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(NOTES_FILE))?;
+        bundle::write_records(io::BufWriter::new(file), notes)?;
+
+        Ok(())
+    }
+
+    /// Re-signs our own notes and profile that are still signed over their bincode encoding, see
+    /// [`note::Signed::verify_legacy`]. Returns how many signatures it replaced. Notes by others keep their
+    /// signatures, as the history isn't verified again.
+    ///
+    /// The notes get new IDs. Our notes listing them as parents are signed again with the new IDs, and the notes read
+    /// follow too. Notes by others still list the old IDs, so they are ordered by clock instead. Receipts and
+    /// deliveries are only kept while running, there are none yet to follow.
+    ///
+    /// Done once per key. The keys done are kept in the store, so later starts don't read every note again.
+    pub fn migrate_signatures(&self, signer: &impl note::Signer) -> Result<usize, Error> {
+        let pub_key: note::PubKey = signer.public().into();
+        let mut done: BTreeSet<note::PubKey> = self.load(MIGRATED_FILE)?.unwrap_or_default();
+        if done.contains(&pub_key) {
+            return Ok(0);
+        }
+
+        let migrate = |notes: &mut Vec<note::Signed<note::Note>>,
+                       ids: &mut BTreeMap<note::NoteId, note::NoteId>|
+         -> Result<usize, Error> {
+            // Parents before their children, which are later by clock.
+            let mut order: Vec<_> = (0..notes.len()).collect();
+            order.sort_by_key(|&i| notes[i].inner.hlc);
+
+            let mut migrated = 0;
+            for i in order {
+                if let Some(resigned) = resign_note(&notes[i], ids, signer)? {
+                    ids.insert(notes[i].id(), resigned.id());
+                    notes[i] = resigned;
+                    migrated += 1;
+                }
+            }
+            Ok(migrated)
+        };
+
+        let mut ids = BTreeMap::new();
+        let mut notes = self.load_notes()?;
+        let mut migrated = migrate(&mut notes, &mut ids)?;
+        if migrated > 0 {
+            let mut records = Vec::new();
+            bundle::write_records(&mut records, &notes)?;
+            self.save_raw(path::Path::new(NOTES_FILE), &records)?;
+        }
+
+        let mut outbox = self.load_outbox()?;
+        let migrated_outbox = migrate(&mut outbox, &mut ids)?;
+        if migrated_outbox > 0 {
+            self.save_outbox(&outbox)?;
+        }
+        migrated += migrated_outbox;
+
+        if !ids.is_empty() {
+            let new_id = |id: &note::NoteId| *ids.get(id).unwrap_or(id);
+            let mut read = self.load_read()?;
+            for notes in read.values_mut() {
+                *notes = notes.iter().map(new_id).collect();
+            }
+            self.save_read(&read)?;
+
+            if let Some(mut markers) =
+                self.load::<BTreeMap<String, (hlc::Timestamp, note::NoteId)>>(LEGACY_READ_FILE)?
+            {
+                for (_, id) in markers.values_mut() {
+                    *id = new_id(id);
+                }
+                self.save(LEGACY_READ_FILE, &markers)?;
+            }
+        }
+
+        let mut profiles = self.load_profiles()?;
+        if let Some(profile) = profiles.get_mut(&pub_key) {
+            if let Some(resigned) = resign(profile, signer)? {
+                *profile = resigned;
+                migrated += 1;
+                self.save_profiles(&profiles)?;
+            }
+        }
+
+        done.insert(pub_key);
+        self.save(MIGRATED_FILE, &done)?;
+
+        Ok(migrated)
+    }
+
+    /// Everything we know, as we left it.
+    pub fn load_model(&self) -> Result<model::Model, Error> {
+        let mut model = model::Model::new();
+        model.contacts = self.load_contacts()?;
+        model.profiles = self.load_profiles()?;
+        for certificate in self.load_certificates()? {
+            model.add_certificate(certificate);
+        }
+        model.blocks = self.load_blocks()?;
+        model.read = self.load_read()?;
+
+        for note in self.load_notes()? {
+            if !model.is_blocked(&note.pub_key) {
+                model.add_note(note);
+            }
+        }
+        for note in self.load_outbox()? {
+            model.add_note(note.clone());
+            model.outbox.insert(note.id(), note);
+        }
+        self.migrate_read_markers(&mut model)?;
+
+        Ok(model)
+    }
+
+    /// We used to keep only the newest note read in each topic, and took everything before it as read. The notes
+    /// before it at the time are read, later ones aren't.
+    fn migrate_read_markers(&self, model: &mut model::Model) -> Result<(), Error> {
+        let Some(markers) =
+            self.load::<BTreeMap<String, (hlc::Timestamp, note::NoteId)>>(LEGACY_READ_FILE)?
+        else {
+            return Ok(());
+        };
+
+        for (topic, marker) in markers {
+            if let Some(notes) = model.topics.get(&topic).map(|topic| &topic.notes) {
+                model
+                    .read
+                    .entry(topic)
+                    .or_default()
+                    .extend(notes.range(..=marker).map(|((_, id), _)| *id));
+            }
+        }
+        self.save_read(&model.read)?;
+        if let Some(dir) = &self.dir {
+            fs::remove_file(dir.join(LEGACY_READ_FILE))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn load_contacts(&self) -> Result<BTreeMap<note::PubKey, model::Contact>, Error> {
+        Ok(self.load(CONTACTS_FILE)?.unwrap_or_default())
+    }
+
+    pub fn save_contacts(
+        &self,
+        contacts: &BTreeMap<note::PubKey, model::Contact>,
+    ) -> Result<(), Error> {
+        self.save(CONTACTS_FILE, contacts)
+    }
+
+    pub fn load_profiles(
+        &self,
+    ) -> Result<BTreeMap<note::PubKey, note::Signed<note::Profile>>, Error> {
+        Ok(self.load(PROFILES_FILE)?.unwrap_or_default())
+    }
+
+    pub fn save_profiles(
+        &self,
+        profiles: &BTreeMap<note::PubKey, note::Signed<note::Profile>>,
+    ) -> Result<(), Error> {
+        self.save(PROFILES_FILE, profiles)
+    }
+
+    pub fn load_certificates(&self) -> Result<Vec<note::Certificate>, Error> {
+        Ok(self.load(CERTIFICATES_FILE)?.unwrap_or_default())
+    }
+
+    pub fn save_certificates(&self, certificates: &[note::Certificate]) -> Result<(), Error> {
+        self.save(CERTIFICATES_FILE, &certificates)
+    }
+
+    /// Keeps the certificate along with the ones we have, to be published next time we're online.
+    pub fn add_certificate(&self, certificate: note::Certificate) -> Result<(), Error> {
+        let mut certificates = self.load_certificates()?;
+        if !certificates.contains(&certificate) {
+            certificates.push(certificate);
+            self.save_certificates(&certificates)?;
+        }
+        Ok(())
+    }
+
+    pub fn load_blocks(&self) -> Result<model::Blocks, Error> {
+        Ok(self.load(BLOCKS_FILE)?.unwrap_or_default())
+    }
+
+    pub fn save_blocks(&self, blocks: &model::Blocks) -> Result<(), Error> {
+        self.save(BLOCKS_FILE, blocks)
+    }
+
+    pub fn load_read(&self) -> Result<BTreeMap<String, BTreeSet<note::NoteId>>, Error> {
+        Ok(self.load(READ_FILE)?.unwrap_or_default())
+    }
+
+    pub fn save_read(&self, read: &BTreeMap<String, BTreeSet<note::NoteId>>) -> Result<(), Error> {
+        self.save(READ_FILE, read)
+    }
+
+    /// Chunk of an attachment, by its hash.
+    pub fn load_chunk(&self, hash: &attachment::Hash) -> Result<Option<Vec<u8>>, Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+
+        match fs::read(dir.join(CHUNKS_DIR).join(hex(hash))) {
+            Ok(chunk) => Ok(Some(chunk)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save_chunk(&self, hash: &attachment::Hash, chunk: &[u8]) -> Result<(), Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        fs::create_dir_all(dir.join(CHUNKS_DIR))?;
+        self.save_raw(&path::Path::new(CHUNKS_DIR).join(hex(hash)), chunk)
+    }
+
+    /// Hashes of the chunks of an attachment, by its root.
+    pub fn load_chunk_list(
+        &self,
+        root: &attachment::Hash,
+    ) -> Result<Option<Vec<attachment::Hash>>, Error> {
+        self.load(&format!("{CHUNKS_DIR}/{}.list", hex(root)))
+    }
+
+    pub fn save_chunk_list(
+        &self,
+        root: &attachment::Hash,
+        chunks: &[attachment::Hash],
+    ) -> Result<(), Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        fs::create_dir_all(dir.join(CHUNKS_DIR))?;
+        self.save(&format!("{CHUNKS_DIR}/{}.list", hex(root)), &chunks)
+    }
+
+    /// The whole file, if we have every chunk of it and they add up to its size. It's read a chunk at a time, so the
+    /// file may be larger than what fits in memory.
+    pub fn open_attachment(
+        &self,
+        attachment: &note::Attachment,
+    ) -> Result<Option<AttachmentReader>, Error> {
+        let (Some(dir), Some(chunks)) = (&self.dir, self.load_chunk_list(&attachment.root)?) else {
+            return Ok(None);
+        };
+
+        let chunks_dir = dir.join(CHUNKS_DIR);
+        let mut size = 0;
+        for hash in &chunks {
+            match fs::metadata(chunks_dir.join(hex(hash))) {
+                Ok(metadata) => size += metadata.len(),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if size != attachment.size {
+            return Err(Error::Size {
+                expected: attachment.size,
+                actual: size,
+            });
+        }
+
+        Ok(Some(AttachmentReader {
+            chunks_dir,
+            chunks: chunks.into_iter(),
+            chunk: None,
+        }))
+    }
+
+    /// Our author identity. A new one is created the first time, and every time for an in-memory store.
+    pub fn load_or_create_identity(&self) -> Result<identity::Keypair, Error> {
+        self.load_or_create_key(IDENTITY_FILE)
+    }
+
+    /// Our author identity, if we have one yet.
+    pub fn load_identity(&self) -> Result<Option<identity::Keypair>, Error> {
+        self.load_key(IDENTITY_FILE)
+    }
+
+    /// Replaces our author identity. The old key is gone for good. Fails for an in-memory store, where the new key
+    /// would be gone right away too.
+    pub fn save_identity(&self, key_pair: &identity::Keypair) -> Result<(), Error> {
+        if self.dir.is_none() {
+            return Err(Error::InMemory);
+        }
+        self.save_key(IDENTITY_FILE, key_pair)
+    }
+
+    /// The key of our relay server, which gives it the peer ID clients are configured with. Kept apart from our
+    /// author identity, so running a relay doesn't tell who we are.
+    pub fn load_or_create_relay_identity(&self) -> Result<identity::Keypair, Error> {
+        self.load_or_create_key(RELAY_IDENTITY_FILE)
+    }
+
+    fn load_or_create_key(&self, name: &str) -> Result<identity::Keypair, Error> {
+        if let Some(key_pair) = self.load_key(name)? {
+            return Ok(key_pair);
+        }
+
+        let key_pair = identity::Keypair::generate_ed25519();
+        self.save_key(name, &key_pair)?;
+        Ok(key_pair)
+    }
+
+    fn load_key(&self, name: &str) -> Result<Option<identity::Keypair>, Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+
+        match fs::read(dir.join(name)) {
+            Ok(bytes) => Ok(Some(identity::Keypair::from_protobuf_encoding(&bytes)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save_key(&self, name: &str, key_pair: &identity::Keypair) -> Result<(), Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let tmp_path = dir.join(format!("{name}.tmp"));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        io::Write::write_all(
+            &mut options.open(&tmp_path)?,
+            &key_pair.to_protobuf_encoding()?,
+        )?;
+        fs::rename(tmp_path, dir.join(name))?;
+
+        Ok(())
+    }
+
+    fn load<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<Option<T>, Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+
+        match fs::File::open(dir.join(name)) {
+            Ok(file) => Ok(Some(T::decode(io::BufReader::new(file))?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save<T: serde::Serialize>(&self, name: &str, value: &T) -> Result<(), Error> {
+        self.save_raw(path::Path::new(name), &value.encode_to_vec()?)
+    }
+
+    /// Writes to a temporary file first, so a crash never leaves a half written file behind.
+    fn save_raw(&self, name: &path::Path, data: &[u8]) -> Result<(), Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let path = dir.join(name);
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("io error")]
+    Io(#[from] io::Error),
+    #[error("encoding error")]
+    Encoding(#[from] bincode::Error),
+    #[error("invalid identity")]
+    Identity(#[from] identity::DecodingError),
+    #[error("invalid note history")]
+    Bundle(#[from] bundle::Error),
+    #[error("signing error")]
+    Signing(#[from] note::SignError),
+    #[error("attachment is {actual} bytes, but its note says {expected} bytes")]
+    Size { expected: u64, actual: u64 },
+    #[error("there is no data directory to keep it in")]
+    InMemory,
+}
+
+/// Reads the chunks of an attachment one after the other, see [`Store::open_attachment`].
+pub struct AttachmentReader {
+    chunks_dir: path::PathBuf,
+    chunks: std::vec::IntoIter<attachment::Hash>,
+    chunk: Option<fs::File>,
+}
+
+impl io::Read for AttachmentReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(chunk) = &mut self.chunk {
+                match io::Read::read(chunk, buf)? {
+                    0 => self.chunk = None,
+                    len => return Ok(len),
+                }
+            }
+
+            let Some(hash) = self.chunks.next() else {
+                return Ok(0);
+            };
+            self.chunk = Some(fs::File::open(self.chunks_dir.join(hex(&hash)))?);
+        }
+    }
+}
+
+const OUTBOX_FILE: &str = "outbox.bin";
+const NOTES_FILE: &str = "notes.log";
+const CONTACTS_FILE: &str = "contacts.bin";
+const PROFILES_FILE: &str = "profiles.bin";
+const CERTIFICATES_FILE: &str = "certificates.bin";
+const BLOCKS_FILE: &str = "blocks.bin";
+const READ_FILE: &str = "read_notes.bin";
+/// Read markers, see [`Store::migrate_read_markers`].
+const LEGACY_READ_FILE: &str = "read.bin";
+/// Keys whose signatures are migrated, see [`Store::migrate_signatures`].
+const MIGRATED_FILE: &str = "migrated_signatures.bin";
+const IDENTITY_FILE: &str = "identity.key";
+const RELAY_IDENTITY_FILE: &str = "relay.key";
+const CHUNKS_DIR: &str = "chunks";
+
+/// The value signed again by us, if it's ours and signed the old way.
+fn resign<T: note::DigestHash + serde::Serialize + Clone>(
+    signed: &note::Signed<T>,
+    signer: &impl note::Signer,
+) -> Result<Option<note::Signed<T>>, Error> {
+    if signed.pub_key != signer.public().into() || signed.verify() || !signed.verify_legacy() {
+        return Ok(None);
+    }
+
+    Ok(Some(signed.inner.clone().sign(signer)?))
+}
+
+/// Our note signed again, if it's signed the old way or lists parents that got new IDs in `ids`.
+fn resign_note(
+    note: &note::Signed<note::Note>,
+    ids: &BTreeMap<note::NoteId, note::NoteId>,
+    signer: &impl note::Signer,
+) -> Result<Option<note::Signed<note::Note>>, Error> {
+    let parents: Vec<_> = note
+        .inner
+        .parents
+        .iter()
+        .map(|parent| *ids.get(parent).unwrap_or(parent))
+        .collect();
+    if parents == note.inner.parents {
+        return resign(note, signer);
+    }
+    if note.pub_key != signer.public().into() || !(note.verify() || note.verify_legacy()) {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        note::Note {
+            parents,
+            ..note.inner.clone()
+        }
+        .sign(signer)?,
+    ))
+}
+
+fn hex(hash: &attachment::Hash) -> String {
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path;
+
+use libp2p::identity;
+
+use crate::attachment;
+use crate::bundle;
+use crate::hlc;
+use crate::model;
+use crate::note;
+use crate::note::Decode as _;
+use crate::note::Encode as _;
+use crate::note::Sign as _;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fake::Fake as _;
+    use rand::RngCore as _;
+    use rand::SeedableRng as _;
+
+    #[test]
+    fn outbox_should_survive_reopening_the_store() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut secret_key_bytes = [0; 32];
+        rng.fill_bytes(&mut secret_key_bytes);
+        let keypair = libp2p::identity::Keypair::ed25519_from_bytes(secret_key_bytes)
+            .expect("Failed to generate keypair");
+
+        let notes: Vec<_> = (0..3)
+            .map(|_| {
+                let note: note::Note = fake::Faker.fake_with_rng(&mut rng);
+                note.sign(&keypair).expect("Failed to sign note")
+            })
+            .collect();
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        Store::open(dir.path())
+            .expect("failed to open store")
+            .save_outbox(&notes)
+            .expect("failed to save outbox");
+
+        let reopened = Store::open(dir.path()).expect("failed to reopen store");
+
+        assert_eq!(
+            reopened.load_outbox().expect("failed to load outbox"),
+            notes
+        );
+    }
+
+    #[test]
+    fn note_history_should_survive_reopening_the_store() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut secret_key_bytes = [0; 32];
+        rng.fill_bytes(&mut secret_key_bytes);
+        let keypair = libp2p::identity::Keypair::ed25519_from_bytes(secret_key_bytes)
+            .expect("Failed to generate keypair");
+        let notes: Vec<_> = (0..3)
+            .map(|_| {
+                let note: note::Note = fake::Faker.fake_with_rng(&mut rng);
+                note.sign(&keypair).expect("Failed to sign note")
+            })
+            .collect();
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let store = Store::open(dir.path()).expect("failed to open store");
+        store
+            .append_notes(&notes[..2])
+            .expect("failed to append notes");
+        store
+            .append_notes(&notes[2..])
+            .expect("failed to append notes");
+
+        let reopened = Store::open(dir.path()).expect("failed to reopen store");
+
+        assert_eq!(reopened.load_notes().expect("failed to load notes"), notes);
+    }
+
+    #[test]
+    fn notes_appended_after_a_crash_should_be_kept() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let keypair = identity::Keypair::generate_ed25519();
+        let notes: Vec<_> = (0..3)
+            .map(|_| {
+                let note: note::Note = fake::Faker.fake_with_rng(&mut rng);
+                note.sign(&keypair).expect("Failed to sign note")
+            })
+            .collect();
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let store = Store::open(dir.path()).expect("failed to open store");
+        store
+            .append_notes(&notes[..2])
+            .expect("failed to append notes");
+        // The crash: half a note at the end.
+        let mut partial = Vec::new();
+        bundle::write_records(&mut partial, &notes[2..]).expect("failed to write record");
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(NOTES_FILE))
+            .expect("failed to open note history");
+        io::Write::write_all(&mut file, &partial[..partial.len() / 2])
+            .expect("failed to write partial note");
+
+        assert_eq!(
+            store.load_notes().expect("failed to load notes"),
+            notes[..2]
+        );
+        store
+            .append_notes(&notes[2..])
+            .expect("failed to append notes");
+
+        let reopened = Store::open(dir.path()).expect("failed to reopen store");
+        assert_eq!(reopened.load_notes().expect("failed to load notes"), notes);
+    }
+
+    /// Signed the way notes were before the canonical encoding.
+    fn legacy_sign<T: serde::Serialize>(inner: T, keypair: &identity::Keypair) -> note::Signed<T> {
+        use sha3::Digest as _;
+
+        let digest = sha3::Keccak256::digest(inner.encode_to_vec().expect("failed to encode"));
+        note::Signed {
+            signature: keypair.sign(&digest).expect("failed to sign"),
+            pub_key: keypair.public().into(),
+            inner,
+        }
+    }
+
+    #[test]
+    fn legacy_signatures_should_be_migrated_for_our_own_notes_only() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let keypair = identity::Keypair::generate_ed25519();
+        let other = identity::Keypair::generate_ed25519();
+        let ours = legacy_sign(
+            fake::Faker.fake_with_rng::<note::Note, _>(&mut rng),
+            &keypair,
+        );
+        let theirs = legacy_sign(fake::Faker.fake_with_rng::<note::Note, _>(&mut rng), &other);
+        let profile = legacy_sign(
+            note::Profile {
+                display_name: "Alice".to_string(),
+                status: String::new(),
+                avatar_hash: None,
+                updated_at: time::OffsetDateTime::now_utc(),
+            },
+            &keypair,
+        );
+        assert!(ours.verify_legacy() && !ours.verify());
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let store = Store::open(dir.path()).expect("failed to open store");
+        store
+            .append_notes([&ours, &theirs])
+            .expect("failed to append notes");
+        store.save_outbox([&ours]).expect("failed to save outbox");
+        store
+            .save_profiles(&BTreeMap::from([(profile.pub_key.clone(), profile)]))
+            .expect("failed to save profiles");
+
+        assert_eq!(store.migrate_signatures(&keypair).ok(), Some(3));
+        assert_eq!(store.migrate_signatures(&keypair).ok(), Some(0));
+        // Once done, the notes aren't looked at again.
+        store.append_notes([&ours]).expect("failed to append notes");
+        assert_eq!(store.migrate_signatures(&keypair).ok(), Some(0));
+
+        let notes = store.load_notes().expect("failed to load notes");
+        assert_eq!(notes[0].inner, ours.inner);
+        assert!(notes[0].verify());
+        assert_eq!(notes[1], theirs);
+        assert_eq!(store.load_outbox().ok(), Some(vec![notes[0].clone()]));
+        assert!(store
+            .load_profiles()
+            .expect("failed to load profiles")
+            .values()
+            .all(note::Signed::verify));
+    }
+
+    #[test]
+    fn migrated_ids_should_be_followed_by_parents_and_notes_read() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let other = identity::Keypair::generate_ed25519();
+        let now = time::OffsetDateTime::now_utc();
+        let note = |i: i64, parents: Vec<note::NoteId>| note::Note {
+            parents,
+            ..note::tests::note_with("ops", format!("note {i}"), now + time::Duration::seconds(i))
+        };
+        let first = legacy_sign(note(0, Vec::new()), &keypair);
+        // Signed the new way already, but after a note that gets a new ID.
+        let second = note(1, vec![first.id()])
+            .sign(&keypair)
+            .expect("failed to sign note");
+        let theirs = note(2, vec![second.id()])
+            .sign(&other)
+            .expect("failed to sign note");
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let store = Store::open(dir.path()).expect("failed to open store");
+        // Out of order, as they came in.
+        store
+            .append_notes([&theirs, &second, &first])
+            .expect("failed to append notes");
+        store.save_outbox([&second]).expect("failed to save outbox");
+        store
+            .save_read(&BTreeMap::from([(
+                "ops".to_string(),
+                BTreeSet::from([first.id(), second.id(), theirs.id()]),
+            )]))
+            .expect("failed to save notes read");
+
+        assert_eq!(store.migrate_signatures(&keypair).ok(), Some(3));
+
+        let notes = store.load_notes().expect("failed to load notes");
+        let [migrated_theirs, migrated_second, migrated_first] = notes.as_slice() else {
+            panic!("expected three notes, got {notes:?}");
+        };
+        assert!(migrated_first.verify() && migrated_second.verify());
+        assert_eq!(migrated_first.inner, first.inner);
+        assert_eq!(migrated_second.inner.parents, vec![migrated_first.id()]);
+        assert_eq!(*migrated_theirs, theirs);
+        assert_eq!(
+            store.load_outbox().ok(),
+            Some(vec![migrated_second.clone()])
+        );
+        assert_eq!(
+            store.load_read().expect("failed to load notes read")["ops"],
+            BTreeSet::from([migrated_first.id(), migrated_second.id(), theirs.id()])
+        );
+
+        let model = store.load_model().expect("failed to load model");
+        assert_eq!(model.unread("ops").notes, 0);
+        assert_eq!(
+            model.topics["ops"].heads,
+            BTreeSet::from([migrated_second.id(), theirs.id()])
+        );
+    }
+
+    #[test]
+    fn identity_should_survive_reopening_the_store() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let key_pair = Store::open(dir.path())
+            .expect("failed to open store")
+            .load_or_create_identity()
+            .expect("failed to create identity");
+
+        let reopened = Store::open(dir.path())
+            .expect("failed to reopen store")
+            .load_or_create_identity()
+            .expect("failed to load identity");
+
+        assert_eq!(reopened.public(), key_pair.public());
+    }
+
+    #[test]
+    fn read_markers_should_be_migrated_to_the_notes_read() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let now = time::OffsetDateTime::now_utc();
+        let notes: Vec<_> = (0..3)
+            .map(|i| {
+                note::tests::note_with("ops", format!("note {i}"), now + time::Duration::seconds(i))
+                    .sign(&keypair)
+                    .expect("failed to sign note")
+            })
+            .collect();
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let store = Store::open(dir.path()).expect("failed to open store");
+        store.append_notes(&notes).expect("failed to append notes");
+        let marker = (notes[1].inner.hlc, notes[1].id());
+        store
+            .save(
+                LEGACY_READ_FILE,
+                &BTreeMap::from([("ops".to_string(), marker)]),
+            )
+            .expect("failed to save read markers");
+
+        let model = store.load_model().expect("failed to load model");
+
+        assert_eq!(
+            model.read["ops"],
+            BTreeSet::from([notes[0].id(), notes[1].id()])
+        );
+        assert_eq!(model.unread("ops").notes, 1);
+        assert_eq!(
+            store.load_model().expect("failed to reload model").read,
+            model.read
+        );
+        assert!(!dir.path().join(LEGACY_READ_FILE).exists());
+    }
+
+    #[test]
+    fn relay_identity_should_survive_reopening_the_store_apart_from_ours() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let store = Store::open(dir.path()).expect("failed to open store");
+        let relay = store
+            .load_or_create_relay_identity()
+            .expect("failed to create relay identity");
+
+        let reopened = Store::open(dir.path()).expect("failed to reopen store");
+
+        assert_eq!(
+            reopened
+                .load_or_create_relay_identity()
+                .expect("failed to load relay identity")
+                .public(),
+            relay.public()
+        );
+        assert!(reopened
+            .load_identity()
+            .expect("failed to load identity")
+            .is_none());
+    }
+
+    #[test]
+    fn contacts_should_survive_reopening_the_store() {
+        let bob: note::PubKey = identity::Keypair::generate_ed25519().public().into();
+        let contacts = BTreeMap::from([(
+            bob.clone(),
+            model::Contact {
+                petname: "Bob".to_string(),
+                notes: "Met at the meetup".to_string(),
+                trust: model::Trust::Verified,
+            },
+        )]);
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        Store::open(dir.path())
+            .expect("failed to open store")
+            .save_contacts(&contacts)
+            .expect("failed to save contacts");
+
+        let model = Store::open(dir.path())
+            .expect("failed to reopen store")
+            .load_model()
+            .expect("failed to load model");
+
+        assert_eq!(model.contacts, contacts);
+        assert_eq!(model.author_name(&bob), "Bob");
+    }
+
+    #[test]
+    fn certificates_should_survive_reopening_the_store() {
+        let old = identity::Keypair::generate_ed25519();
+        let new = identity::Keypair::generate_ed25519();
+        let rotation = note::Certificate::Rotation(
+            note::Rotation {
+                new_key: new.public().into(),
+                rotated_at: time::OffsetDateTime::now_utc(),
+            }
+            .sign(&old)
+            .expect("failed to sign rotation"),
+        );
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let store = Store::open(dir.path()).expect("failed to open store");
+        store
+            .add_certificate(rotation.clone())
+            .expect("failed to add certificate");
+        store
+            .add_certificate(rotation.clone())
+            .expect("failed to add certificate");
+
+        let model = Store::open(dir.path())
+            .expect("failed to reopen store")
+            .load_model()
+            .expect("failed to load model");
+
+        assert_eq!(model.keys.certificates(), vec![rotation]);
+        assert_eq!(
+            model.keys.current(&old.public().into()),
+            &new.public().into()
+        );
+    }
+
+    #[test]
+    fn in_memory_store_should_start_empty() {
+        let store = Store::in_memory();
+
+        assert!(store
+            .load_outbox()
+            .expect("failed to load outbox")
+            .is_empty());
+    }
+}
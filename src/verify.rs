@@ -0,0 +1,289 @@
+//! Signature checks in bulk, off the async task.
+//!
+//! Ed25519 signatures are checked in batches, which takes about half the time per signature. A batch only tells
+//! whether all of its signatures are good, so one with a bad signature is checked again one by one.
+//!
+//! Both go through `ed25519-zebra`, which follows ZIP 215: the cofactored equation `[8]([s]B - R - [k]A) = 0`, alone
+//! and in batches alike, so a batch passes exactly the signatures a single check does. libp2p's own check refuses
+//! signatures with crafted small order components, which no honest signer makes. Keys of small order are refused,
+//! anyone could sign for them.
+//!
+//! [`Verifier`] runs batches on the blocking pool, so several run at once and none holds up the UI.
+
+/// Most signatures checked in one batch.
+pub const MAX_BATCH_SIZE: usize = 256;
+
+/// Whether each signature is good, as [`note::Signed::verify`] would say.
+pub fn verify_batch<T: note::DigestHash>(signed: &[note::Signed<T>]) -> Vec<bool> {
+    let mut valid = vec![false; signed.len()];
+
+    let mut batch = batch::Verifier::new();
+    let mut items = Vec::new();
+    for (index, signed) in signed.iter().enumerate() {
+        match signed.pub_key.ed25519_bytes() {
+            Some(key) => {
+                if let Some(item) =
+                    ed25519_item(key, &signed.inner.digest_hash(), &signed.signature)
+                {
+                    batch.queue(item.clone());
+                    items.push((index, item));
+                }
+            }
+            None => valid[index] = signed.verify(),
+        }
+    }
+
+    let all_valid = batch.verify(rand::thread_rng()).is_ok();
+    for (index, item) in items {
+        valid[index] = all_valid || item.verify_single().is_ok();
+    }
+
+    valid
+}
+
+/// Whether `signature` is `key`'s on `message`. See the [module](self) for how that differs from libp2p's check.
+pub(crate) fn verify_ed25519(key: [u8; 32], message: &[u8], signature: &[u8]) -> bool {
+    ed25519_item(key, message, signature).is_some_and(|item| item.verify_single().is_ok())
+}
+
+/// Fails for anything that can't be a good signature, whatever the equation says.
+fn ed25519_item(key: [u8; 32], message: &[u8], signature: &[u8]) -> Option<batch::Item> {
+    let signature = ed25519_zebra::Signature::from_slice(signature).ok()?;
+    if CompressedEdwardsY(key).decompress()?.is_small_order() {
+        return None;
+    }
+
+    Some(batch::Item::from((
+        ed25519_zebra::VerificationKeyBytes::from(key),
+        signature,
+        message,
+    )))
+}
+
+/// Checks signatures on the blocking pool, and hands them back batch by batch with whatever was pushed along with them.
+pub struct Verifier<C, T> {
+    queue: Vec<(C, note::Signed<T>)>,
+    running: FuturesUnordered<tokio::task::JoinHandle<Vec<Verified<C, T>>>>,
+}
+
+pub struct Verified<C, T> {
+    pub context: C,
+    pub signed: note::Signed<T>,
+    pub valid: bool,
+}
+
+impl<C, T> Verifier<C, T>
+where
+    C: Send + 'static,
+    T: note::DigestHash + Send + 'static,
+{
+    pub fn new() -> Self {
+        Self {
+            queue: Vec::new(),
+            running: FuturesUnordered::new(),
+        }
+    }
+
+    pub fn push(&mut self, context: C, signed: note::Signed<T>) {
+        self.queue.push((context, signed));
+    }
+
+    /// Whether everything pushed has been handed back.
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty() && self.running.is_empty()
+    }
+
+    /// The next batch that's been checked. Cancel safe, so it can wait in a `tokio::select!`, and never finishes while
+    /// there is nothing to check.
+    pub async fn next(&mut self) -> Vec<Verified<C, T>> {
+        self.start();
+        match self.running.next().await {
+            Some(verified) => verified.expect("signature checks don't panic"),
+            None => futures::future::pending().await,
+        }
+    }
+
+    /// While batches are running, signatures queue up until they fill one. Otherwise they are checked right away.
+    fn start(&mut self) {
+        while self.queue.len() >= MAX_BATCH_SIZE
+            || (self.running.is_empty() && !self.queue.is_empty())
+        {
+            let batch: Vec<_> = self
+                .queue
+                .drain(..self.queue.len().min(MAX_BATCH_SIZE))
+                .collect();
+            self.running.push(tokio::task::spawn_blocking(move || {
+                let (contexts, signed): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+                let valid = verify_batch(&signed);
+                contexts
+                    .into_iter()
+                    .zip(signed)
+                    .zip(valid)
+                    .map(|((context, signed), valid)| Verified {
+                        context,
+                        signed,
+                        valid,
+                    })
+                    .collect()
+            }));
+        }
+    }
+}
+
+impl<C, T> Default for Verifier<C, T>
+where
+    C: Send + 'static,
+    T: note::DigestHash + Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_zebra::batch;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt as _;
+
+use crate::note;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use curve25519_dalek::edwards::EdwardsPoint;
+    use curve25519_dalek::scalar::Scalar;
+    use fake::Fake as _;
+    use libp2p::identity;
+    use note::Sign as _;
+    use sha2::Digest as _;
+
+    fn signed_notes(count: usize) -> Vec<note::Signed<note::Note>> {
+        let key_pairs = [
+            identity::Keypair::generate_ed25519(),
+            identity::Keypair::generate_ed25519(),
+        ];
+        (0..count)
+            .map(|i| {
+                fake::Faker
+                    .fake::<note::Note>()
+                    .sign(&key_pairs[i % 2])
+                    .expect("failed to sign note")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batches_should_tell_bad_signatures_apart() {
+        let mut notes = signed_notes(10);
+        assert_eq!(verify_batch(&notes), vec![true; 10]);
+
+        notes[3].inner.msg.push_str("TAMPERED");
+        notes[7].signature.pop();
+        notes[8].pub_key = notes[9].pub_key.clone();
+
+        let valid = verify_batch(&notes);
+        assert_eq!(
+            valid,
+            notes.iter().map(note::Signed::verify).collect::<Vec<_>>()
+        );
+        assert_eq!(valid.iter().filter(|valid| !**valid).count(), 3);
+    }
+
+    /// A good signature, but for a commitment `R` with a component of small order, which honest signers don't make.
+    fn sign_with_torsion(
+        note: note::Note,
+        key_pair: &identity::Keypair,
+    ) -> note::Signed<note::Note> {
+        let key_pair = key_pair
+            .clone()
+            .try_into_ed25519()
+            .expect("not an Ed25519 key");
+        let mut expanded: [u8; 32] = sha2::Sha512::digest(key_pair.secret().as_ref())[..32]
+            .try_into()
+            .expect("SHA-512 is 64 bytes");
+        expanded[0] &= 248;
+        expanded[31] &= 127;
+        expanded[31] |= 64;
+        let secret = Scalar::from_bytes_mod_order(expanded);
+        let key = key_pair.public().to_bytes();
+
+        let nonce = Scalar::from(rand::random::<u128>());
+        let commitment = (EdwardsPoint::mul_base(&nonce)
+            + curve25519_dalek::constants::EIGHT_TORSION[1])
+            .compress();
+        let challenge = sha2::Sha512::new()
+            .chain_update(commitment.as_bytes())
+            .chain_update(key)
+            .chain_update(note::DigestHash::digest_hash(&note))
+            .finalize();
+        let s = nonce + Scalar::from_bytes_mod_order_wide(&challenge.into()) * secret;
+
+        note::Signed {
+            inner: note,
+            pub_key: identity::PublicKey::from(key_pair.public()).into(),
+            signature: [commitment.to_bytes(), s.to_bytes()].concat(),
+        }
+    }
+
+    #[test]
+    fn batches_should_agree_with_single_checks_on_small_order_components() {
+        let key_pair = identity::Keypair::generate_ed25519();
+        let crafted = sign_with_torsion(fake::Faker.fake(), &key_pair);
+        let digest = note::DigestHash::digest_hash(&crafted.inner);
+        assert!(!key_pair.public().verify(&digest, &crafted.signature));
+        assert!(crafted.verify());
+
+        // A random batch would pass it some of the time, if the equations weren't cofactored.
+        for _ in 0..32 {
+            let mut notes = signed_notes(4);
+            notes.push(crafted.clone());
+            assert_eq!(verify_batch(&notes), vec![true; 5]);
+        }
+    }
+
+    #[test]
+    fn keys_of_small_order_should_be_refused() {
+        let identity_point: [u8; 32] = EdwardsPoint::default().compress().to_bytes();
+        let weak_key = identity::ed25519::PublicKey::try_from_bytes(&identity_point)
+            .expect("failed to decode key");
+        // Any s makes a signature for a key of small order, with R = [s]B.
+        let s = Scalar::from(42u8);
+        let forged = note::Signed {
+            inner: fake::Faker.fake::<note::Note>(),
+            pub_key: identity::PublicKey::from(weak_key).into(),
+            signature: [
+                EdwardsPoint::mul_base(&s).compress().to_bytes(),
+                s.to_bytes(),
+            ]
+            .concat(),
+        };
+
+        assert!(!forged.verify());
+        assert_eq!(verify_batch(&[forged]), vec![false]);
+    }
+
+    #[tokio::test]
+    async fn verifiers_should_hand_back_everything_pushed() {
+        let mut notes = signed_notes(MAX_BATCH_SIZE * 2 + 10);
+        notes[42].inner.topic.push_str("TAMPERED");
+
+        let mut verifier = Verifier::new();
+        for (index, note) in notes.iter().enumerate() {
+            verifier.push(index, note.clone());
+        }
+
+        let mut verified = Vec::new();
+        while !verifier.is_idle() {
+            verified.extend(verifier.next().await);
+        }
+        verified.sort_by_key(|verified| verified.context);
+
+        assert_eq!(verified.len(), notes.len());
+        for (index, verified) in verified.into_iter().enumerate() {
+            assert_eq!(verified.context, index);
+            assert_eq!(verified.signed, notes[index]);
+            assert_eq!(verified.valid, index != 42);
+        }
+    }
+}